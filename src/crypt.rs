@@ -0,0 +1,798 @@
+//! The standard security handler: working out the key of an encrypted file from a password,
+//! and decrypting its strings and streams with RC4 or AES.
+
+mod aes;
+mod md5;
+mod rc4;
+mod sha2;
+
+use core::fmt;
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{Dict, Name, Object, ObjectId};
+use md5::md5;
+use rc4::rc4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CryptError {
+    /// An encryption scheme this parser doesn't implement, like public-key security handlers.
+    Unsupported(String),
+    /// The password is neither the user nor the owner password.
+    WrongPassword,
+    /// The `/Encrypt` dictionary, or some data encrypted with it, is malformed.
+    Corrupt(String),
+}
+
+impl fmt::Display for CryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptError::Unsupported(what) => write!(f, "unsupported encryption: {}", what),
+            CryptError::WrongPassword => write!(f, "incorrect password for encrypted file"),
+            CryptError::Corrupt(message) => write!(f, "corrupt encryption: {}", message),
+        }
+    }
+}
+
+/// What passwords are padded (or truncated) to 32 bytes with, in revisions 2 to 4.
+const PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// How a crypt filter encrypts data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    /// Not at all.
+    Identity,
+    Rc4,
+    /// AES-128 in CBC mode, with keys made per object like RC4's.
+    Aes128,
+    /// AES-256 in CBC mode, with the file key used for every object.
+    Aes256,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Identity => write!(f, "none"),
+            Method::Rc4 => write!(f, "RC4"),
+            Method::Aes128 => write!(f, "AES-128"),
+            Method::Aes256 => write!(f, "AES-256"),
+        }
+    }
+}
+
+/// What `/P` allows someone who opened the file with the user password to do. The owner
+/// password allows everything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Permissions {
+    bits: u32,
+    /// Revision 2 has fewer flags, and the later ones fall back on its coarser ones.
+    revision: i64,
+}
+
+impl Permissions {
+    /// Bit `n` of `/P`, counting from 1 like the spec does.
+    fn bit(self, n: u32) -> bool {
+        self.bits & (1 << (n - 1)) != 0
+    }
+
+    pub fn bits(self) -> i32 {
+        self.bits as i32
+    }
+
+    pub fn print(self) -> bool {
+        self.bit(3)
+    }
+
+    pub fn modify(self) -> bool {
+        self.bit(4)
+    }
+
+    /// Copying or otherwise extracting text and graphics.
+    pub fn copy(self) -> bool {
+        self.bit(5)
+    }
+
+    /// Adding and changing annotations, and filling in forms.
+    pub fn annotate(self) -> bool {
+        self.bit(6)
+    }
+
+    pub fn fill_forms(self) -> bool {
+        self.annotate() || self.revision >= 3 && self.bit(9)
+    }
+
+    /// Inserting, rotating and deleting pages, and making bookmarks and thumbnails.
+    pub fn assemble(self) -> bool {
+        self.modify() || self.revision >= 3 && self.bit(11)
+    }
+
+    /// Printing at full quality, rather than only a low-resolution rendering.
+    pub fn print_high_quality(self) -> bool {
+        self.print() && (self.revision < 3 || self.bit(12))
+    }
+
+    /// Every permission, with its name.
+    pub fn list(self) -> [(&'static str, bool); 7] {
+        [
+            ("print", self.print()),
+            ("copy", self.copy()),
+            ("modify", self.modify()),
+            ("annotate", self.annotate()),
+            ("fill forms", self.fill_forms()),
+            ("assemble", self.assemble()),
+            ("high-quality print", self.print_high_quality()),
+        ]
+    }
+}
+
+/// The `/Encrypt` entries the key derivation of revisions 2 to 4 depends on.
+struct Params<'e> {
+    revision: i64,
+    key_len: usize,
+    owner_hash: &'e [u8],
+    user_hash: &'e [u8],
+    permissions: u32,
+    file_id: &'e [u8],
+    encrypt_metadata: bool,
+}
+
+impl Params<'_> {
+    /// The file key, assuming `password` is the user password (algorithm 2).
+    fn file_key(&self, password: &[u8]) -> Vec<u8> {
+        let mut input = pad_password(password).to_vec();
+        input.extend_from_slice(self.owner_hash);
+        input.extend_from_slice(&self.permissions.to_le_bytes());
+        input.extend_from_slice(self.file_id);
+        if self.revision >= 4 && !self.encrypt_metadata {
+            input.extend_from_slice(&[0xFF; 4]);
+        }
+        let mut hash = md5(&input);
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                hash = md5(&hash[..self.key_len]);
+            }
+        }
+        hash[..self.key_len].to_vec()
+    }
+
+    /// Whether `key` is right, by checking it reproduces `/U` (algorithms 4 and 5).
+    fn is_user_key(&self, key: &[u8]) -> bool {
+        if self.revision == 2 {
+            return rc4(key, &PADDING) == self.user_hash;
+        }
+        let mut hash = md5(&[&PADDING[..], self.file_id].concat()).to_vec();
+        for i in 0..20 {
+            hash = rc4(&xor_key(key, i), &hash);
+        }
+        // The rest of `/U` is arbitrary padding
+        self.user_hash.starts_with(&hash)
+    }
+
+    /// The user password, assuming `password` is the owner password (algorithm 7). `/O` is
+    /// the user password, encrypted with a key made from the owner password.
+    fn user_password(&self, password: &[u8]) -> Vec<u8> {
+        let mut hash = md5(&pad_password(password));
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                hash = md5(&hash);
+            }
+        }
+        let key = &hash[..self.key_len];
+        if self.revision == 2 {
+            return rc4(key, self.owner_hash);
+        }
+        let mut user_password = self.owner_hash.to_vec();
+        for i in (0..20).rev() {
+            user_password = rc4(&xor_key(key, i), &user_password);
+        }
+        user_password
+    }
+}
+
+/// `password`, padded or truncated to 32 bytes.
+fn pad_password(password: &[u8]) -> [u8; 32] {
+    let len = password.len().min(32);
+    let mut padded = [0; 32];
+    padded[..len].copy_from_slice(&password[..len]);
+    padded[len..].copy_from_slice(&PADDING[..32 - len]);
+    padded
+}
+
+/// `key` with every byte XORed with `i`, for the extra RC4 passes of revision 3 and up.
+fn xor_key(key: &[u8], i: u8) -> Vec<u8> {
+    key.iter().map(|byte| byte ^ i).collect()
+}
+
+/// The hash of a password with an 8 byte salt, in revisions 5 and 6 (algorithm 2.B).
+/// `user_hash` is the first 48 bytes of `/U` when checking the owner password, and empty
+/// otherwise.
+fn password_hash(revision: i64, password: &[u8], salt: &[u8], user_hash: &[u8]) -> Vec<u8> {
+    let mut hash = sha2::sha256(&[password, salt, user_hash].concat()).to_vec();
+    if revision == 5 {
+        return hash;
+    }
+
+    let mut round = 0;
+    loop {
+        let block = [password, &hash, user_hash].concat().repeat(64);
+        let iv = hash[16..32].try_into().unwrap();
+        let encrypted = aes::cbc_encrypt(&hash[..16], iv, &block);
+        // The sum of the first 16 bytes mod 3 picks the next hash function
+        let sum: u32 = encrypted[..16].iter().map(|&byte| byte as u32).sum();
+        hash = match sum % 3 {
+            0 => sha2::sha256(&encrypted).to_vec(),
+            1 => sha2::sha384(&encrypted).to_vec(),
+            _ => sha2::sha512(&encrypted).to_vec(),
+        };
+        round += 1;
+        if round >= 64 && encrypted[encrypted.len() - 1] as usize <= round - 32 {
+            break;
+        }
+    }
+    hash.truncate(32);
+    hash
+}
+
+/// Decrypts the strings and streams of a file encrypted by the standard security handler.
+#[derive(Clone, Debug)]
+pub struct SecurityHandler {
+    /// The algorithm (`/V`) and the revision of the standard security handler (`/R`).
+    pub version: i64,
+    pub revision: i64,
+    key: Vec<u8>,
+    /// The methods for strings and streams, given by `/StrF` and `/StmF`.
+    pub strings: Method,
+    pub streams: Method,
+    /// Crypt filters by name, for streams that pick their own with a `/Crypt` filter.
+    pub filters: HashMap<Name<'static>, Method>,
+    /// Whether metadata streams are encrypted too.
+    pub encrypt_metadata: bool,
+    /// Whether the password was the owner password rather than the user password.
+    pub owner: bool,
+    pub permissions: Permissions,
+}
+
+impl SecurityHandler {
+    /// Works out the file key from the `/Encrypt` dictionary `encrypt`, the first element
+    /// of the trailer's `/ID`, and either the user or the owner password.
+    pub(crate) fn new(encrypt: &Dict, file_id: &[u8], password: &[u8]) -> Result<Self, CryptError> {
+        let corrupt = |message: &str| CryptError::Corrupt(message.to_owned());
+        match encrypt.get("Filter").and_then(Object::as_name) {
+            Some("Standard") => {}
+            Some(other) => {
+                return Err(CryptError::Unsupported(format!(
+                    "`{}` security handler",
+                    other
+                )));
+            }
+            None => return Err(corrupt("`/Encrypt` has no `/Filter`")),
+        }
+        let int = |key| encrypt.get(key).and_then(Object::as_int);
+        let string = |key| match encrypt.get(key) {
+            Some(Object::String(s)) => Ok(s.as_slice()),
+            _ => Err(CryptError::Corrupt(format!("`/{}` must be a string", key))),
+        };
+        let version = int("V").unwrap_or(0);
+        let revision = int("R").ok_or_else(|| corrupt("`/R` must be an integer"))?;
+        let permissions = int("P").ok_or_else(|| corrupt("`/P` must be an integer"))?;
+        let encrypt_metadata = encrypt
+            .get("EncryptMetadata")
+            .and_then(Object::as_bool)
+            .unwrap_or(true);
+
+        let mut filters = HashMap::from([(Name::from("Identity"), Method::Identity)]);
+        let (strings, streams) = match version {
+            1 | 2 => (Method::Rc4, Method::Rc4),
+            4 | 5 => {
+                if let Some(Object::Dict(crypt_filters)) = encrypt.get("CF") {
+                    for (name, filter) in crypt_filters {
+                        let method = filter
+                            .as_dict()
+                            .and_then(|filter| filter.get("CFM"))
+                            .and_then(Object::as_name);
+                        let method = match method {
+                            None | Some("None") => Method::Identity,
+                            Some("V2") => Method::Rc4,
+                            Some("AESV2") => Method::Aes128,
+                            Some("AESV3") => Method::Aes256,
+                            Some(other) => {
+                                return Err(CryptError::Unsupported(format!(
+                                    "`{}` crypt filter method",
+                                    other
+                                )));
+                            }
+                        };
+                        filters.insert(name.clone().into_owned(), method);
+                    }
+                }
+                let pick = |key| match encrypt.get(key).and_then(Object::as_name) {
+                    None => Ok(Method::Identity),
+                    Some(name) => filters.get(name.as_bytes()).copied().ok_or_else(|| {
+                        CryptError::Corrupt(format!("`/{}` is an unknown crypt filter", key))
+                    }),
+                };
+                (pick("StrF")?, pick("StmF")?)
+            }
+            _ => {
+                return Err(CryptError::Unsupported(format!(
+                    "encryption algorithm `/V {}`",
+                    version
+                )));
+            }
+        };
+
+        let (key, owner) = match revision {
+            2..=4 => {
+                // `/Length` is in bits, and only revision 2 is stuck with 40 bit keys
+                let key_len = match revision {
+                    2 => 5,
+                    _ => (int("Length").unwrap_or(if version == 4 { 128 } else { 40 }) / 8)
+                        .clamp(5, 16) as usize,
+                };
+                let (owner_hash, user_hash) = (string("O")?, string("U")?);
+                if owner_hash.len() < 32 || user_hash.len() < 32 {
+                    return Err(corrupt("`/O` and `/U` must be 32 bytes long"));
+                }
+                let params = Params {
+                    revision,
+                    key_len,
+                    owner_hash: &owner_hash[..32],
+                    user_hash: &user_hash[..32],
+                    permissions: permissions as u32,
+                    file_id,
+                    encrypt_metadata,
+                };
+                // Try the owner password first, since it might be the user password too
+                let owner_key = params.file_key(&params.user_password(password));
+                if params.is_user_key(&owner_key) {
+                    (owner_key, true)
+                } else {
+                    let user_key = params.file_key(password);
+                    if !params.is_user_key(&user_key) {
+                        return Err(CryptError::WrongPassword);
+                    }
+                    (user_key, false)
+                }
+            }
+            5 | 6 => {
+                // Passwords are UTF-8, cut off at 127 bytes
+                let password = &password[..password.len().min(127)];
+                let (owner_hash, user_hash) = (string("O")?, string("U")?);
+                let (owner_key, user_key) = (string("OE")?, string("UE")?);
+                if owner_hash.len() < 48 || user_hash.len() < 48 {
+                    return Err(corrupt("`/O` and `/U` must be 48 bytes long"));
+                }
+                if owner_key.len() < 32 || user_key.len() < 32 {
+                    return Err(corrupt("`/OE` and `/UE` must be 32 bytes long"));
+                }
+                // Each hash is followed by a salt to check the password with, and a salt to
+                // make the key that decrypts the file key with
+                let hash =
+                    |salt: &[u8], user_hash| password_hash(revision, password, salt, user_hash);
+                let (encrypted_key, key_hash, owner) =
+                    if hash(&owner_hash[32..40], &user_hash[..48]) == owner_hash[..32] {
+                        (owner_key, hash(&owner_hash[40..48], &user_hash[..48]), true)
+                    } else if hash(&user_hash[32..40], &[]) == user_hash[..32] {
+                        (user_key, hash(&user_hash[40..48], &[]), false)
+                    } else {
+                        return Err(CryptError::WrongPassword);
+                    };
+                let key = aes::cbc_decrypt(&key_hash, &[0; 16], &encrypted_key[..32]);
+                (key, owner)
+            }
+            _ => {
+                return Err(CryptError::Unsupported(format!(
+                    "standard security handler revision {}",
+                    revision
+                )));
+            }
+        };
+
+        Ok(Self {
+            version,
+            revision,
+            key,
+            strings,
+            streams,
+            filters,
+            encrypt_metadata,
+            owner,
+            permissions: Permissions {
+                bits: permissions as u32,
+                revision,
+            },
+        })
+    }
+
+    pub fn key_bits(&self) -> usize {
+        self.key.len() * 8
+    }
+
+    /// The key for the strings and streams of object `id` (algorithm 1).
+    fn object_key(&self, id: ObjectId, method: Method) -> Vec<u8> {
+        if method == Method::Aes256 {
+            return self.key.clone();
+        }
+        let mut input = self.key.clone();
+        input.extend_from_slice(&(id.num as u32).to_le_bytes()[..3]);
+        input.extend_from_slice(&id.gen.to_le_bytes());
+        if method == Method::Aes128 {
+            input.extend_from_slice(b"sAlT");
+        }
+        let len = (self.key.len() + 5).min(16);
+        md5(&input)[..len].to_vec()
+    }
+
+    fn decrypt<'d>(
+        &self,
+        id: ObjectId,
+        method: Method,
+        data: &'d [u8],
+    ) -> Result<Cow<'d, [u8]>, CryptError> {
+        let key = self.object_key(id, method);
+        match method {
+            Method::Identity => Ok(Cow::Borrowed(data)),
+            Method::Rc4 => Ok(Cow::Owned(rc4(&key, data))),
+            // Some writers leave empty strings as they are
+            Method::Aes128 | Method::Aes256 if data.is_empty() => Ok(Cow::Borrowed(data)),
+            Method::Aes128 | Method::Aes256 => {
+                // The data starts with the initialization vector, and is padded to whole blocks
+                if data.len() < 32 || data.len() % 16 != 0 {
+                    return Err(CryptError::Corrupt(format!(
+                        "AES-encrypted data in object {} is {} bytes long",
+                        id,
+                        data.len()
+                    )));
+                }
+                let (iv, blocks) = data.split_at(16);
+                let mut plain = aes::cbc_decrypt(&key, iv.try_into().unwrap(), blocks);
+                let padding = plain[plain.len() - 1];
+                let valid = (1..=16).contains(&padding)
+                    && plain[plain.len() - padding as usize..]
+                        .iter()
+                        .all(|&byte| byte == padding);
+                if !valid {
+                    return Err(CryptError::Corrupt(format!(
+                        "AES-encrypted data in object {} is padded wrongly, so the key is wrong",
+                        id
+                    )));
+                }
+                plain.truncate(plain.len() - padding as usize);
+                Ok(Cow::Owned(plain))
+            }
+        }
+    }
+
+    /// `obj`, part of object `id`, with all its strings decrypted, including those in stream
+    /// dictionaries. Stream data is left to [`SecurityHandler::decrypt_stream`].
+    pub(crate) fn decrypt_object<'a>(
+        &self,
+        id: ObjectId,
+        obj: Object<'a>,
+    ) -> Result<Object<'a>, CryptError> {
+        let decrypt_dict = |dict: Dict<'a>| {
+            dict.into_iter()
+                .map(|(key, value)| Ok((key, self.decrypt_object(id, value)?)))
+                .collect::<Result<Dict<'a>, CryptError>>()
+        };
+        Ok(match obj {
+            Object::String(s) => Object::String(self.decrypt(id, self.strings, &s)?.into_owned()),
+            Object::Array(arr) => Object::Array(
+                arr.into_iter()
+                    .map(|obj| self.decrypt_object(id, obj))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Object::Dict(dict) => Object::Dict(decrypt_dict(dict)?),
+            // Cross-reference streams are the trailer, which isn't encrypted
+            Object::Stream { ref dict, .. }
+                if dict.get("Type").and_then(Object::as_name) == Some("XRef") =>
+            {
+                obj
+            }
+            Object::Stream {
+                dict,
+                data,
+                decoded,
+            } => Object::Stream {
+                dict: decrypt_dict(dict)?,
+                data,
+                decoded,
+            },
+            obj => obj,
+        })
+    }
+
+    /// How stream `id`, with dictionary `dict`, is encrypted. Cross-reference streams are never
+    /// encrypted, and metadata streams may not be.
+    fn stream_method(&self, id: ObjectId, dict: &Dict) -> Result<Method, CryptError> {
+        match dict.get("Type").and_then(Object::as_name) {
+            Some("XRef") => return Ok(Method::Identity),
+            Some("Metadata") if !self.encrypt_metadata => return Ok(Method::Identity),
+            _ => {}
+        }
+
+        // A `/Crypt` filter can only come first, and names the crypt filter to use instead
+        let first_filter = match dict.get("Filter") {
+            Some(Object::Array(filters)) => filters.first(),
+            filter => filter,
+        };
+        if first_filter.and_then(Object::as_name) != Some("Crypt") {
+            return Ok(self.streams);
+        }
+        let parms = match dict.get("DecodeParms") {
+            Some(Object::Array(parms)) => parms.first(),
+            parms => parms,
+        };
+        let name = parms
+            .and_then(Object::as_dict)
+            .and_then(|parms| parms.get("Name"))
+            .and_then(Object::as_name)
+            .unwrap_or("Identity");
+        self.filters.get(name.as_bytes()).copied().ok_or_else(|| {
+            CryptError::Corrupt(format!(
+                "object {} uses an unknown crypt filter `{}`",
+                id, name
+            ))
+        })
+    }
+
+    /// The data of stream `id`, with dictionary `dict`, decrypted.
+    pub(crate) fn decrypt_stream<'d>(
+        &self,
+        id: ObjectId,
+        dict: &Dict,
+        data: Cow<'d, [u8]>,
+    ) -> Result<Cow<'d, [u8]>, CryptError> {
+        let method = self.stream_method(id, dict)?;
+        let decrypted = match self.decrypt(id, method, &data)? {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(decrypted) => Some(decrypted),
+        };
+        Ok(decrypted.map_or(data, Cow::Owned))
+    }
+}
+
+/// The bytes that `hex` spells out, for writing test vectors.
+#[cfg(test)]
+pub(crate) fn hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vectors made with an independent implementation of the spec's algorithms, for the
+    // user password `user` (`üser` for revision 6) and the owner password `owner`
+    const FILE_ID: &str = "0123456789abcdef0123456789abcdef";
+
+    /// A standard security handler's `/Encrypt` dictionary, with `/P -3904` and, for `/V 4`
+    /// and up, the crypt filter method `cfm` for both strings and streams.
+    fn encrypt(
+        ints: &[(&'static str, i64)],
+        cfm: Option<&'static str>,
+        strings: &[(&'static str, &str)],
+    ) -> Dict<'static> {
+        let mut dict = Dict::default();
+        dict.insert(Name::from("Filter"), Object::Name(Name::from("Standard")));
+        dict.insert(Name::from("P"), Object::Int(-3904));
+        for &(key, value) in ints {
+            dict.insert(Name::from(key), Object::Int(value));
+        }
+        for &(key, value) in strings {
+            dict.insert(Name::from(key), Object::String(hex(value)));
+        }
+        if let Some(cfm) = cfm {
+            let filter = Dict::from_iter([(Name::from("CFM"), Object::Name(Name::from(cfm)))]);
+            let filters = Dict::from_iter([(Name::from("StdCF"), Object::Dict(filter))]);
+            dict.insert(Name::from("CF"), Object::Dict(filters));
+            dict.insert(Name::from("StmF"), Object::Name(Name::from("StdCF")));
+            dict.insert(Name::from("StrF"), Object::Name(Name::from("StdCF")));
+        }
+        dict
+    }
+
+    /// Checks both passwords give the file key `key`, which decrypts `encrypted`, a string
+    /// in object 5, and that any other password is wrong.
+    fn check(encrypt: &Dict, user: &str, key: &str, encrypted: &str) {
+        let id = ObjectId { num: 5, gen: 0 };
+        for (password, owner) in [(user, false), ("owner", true)] {
+            let handler = SecurityHandler::new(encrypt, &hex(FILE_ID), password.as_bytes())
+                .unwrap_or_else(|err| panic!("{:?}: {}", password, err));
+            assert_eq!(handler.owner, owner, "{:?}", password);
+            assert_eq!(handler.key, hex(key), "{:?}", password);
+            match handler.decrypt_object(id, Object::String(hex(encrypted))) {
+                Ok(Object::String(s)) => assert_eq!(s, b"Secret message"),
+                other => panic!("{:?}: {:?}", password, other),
+            }
+        }
+        for password in ["", "wrong"] {
+            assert_eq!(
+                SecurityHandler::new(encrypt, &hex(FILE_ID), password.as_bytes()).unwrap_err(),
+                CryptError::WrongPassword
+            );
+        }
+    }
+
+    #[test]
+    fn revision_2() {
+        let encrypt = encrypt(
+            &[("V", 1), ("R", 2)],
+            None,
+            &[
+                (
+                    "O",
+                    "94e8094419662a774442fb072e3d9f19e9d130ec09a4d0061e78fe920f7ab62f",
+                ),
+                (
+                    "U",
+                    "ed99f758c683e69c67775cddf1dc7da0e3f0f38fc0fed1b0c6a4e0c91f8945b8",
+                ),
+            ],
+        );
+        check(
+            &encrypt,
+            "user",
+            "393b74dcec",
+            "43951b60faeb348626fde073a221",
+        );
+    }
+
+    #[test]
+    fn revision_3() {
+        let encrypt = encrypt(
+            &[("V", 2), ("R", 3), ("Length", 128)],
+            None,
+            &[
+                (
+                    "O",
+                    "0ba3835f88f90388e74e54584125ce142be0de24c6b0d37746e075b891756671",
+                ),
+                (
+                    "U",
+                    "ad944bba9714b0f0d9da179955e19450751d591be537aa9d0aa5dcb3b12155c1",
+                ),
+            ],
+        );
+        check(
+            &encrypt,
+            "user",
+            "f39f55714572d7d71ba77d681118d8b6",
+            "758e8fde0eec1d1890965604db5c",
+        );
+    }
+
+    #[test]
+    fn revision_4_rc4() {
+        let encrypt = encrypt(
+            &[("V", 4), ("R", 4), ("Length", 128)],
+            Some("V2"),
+            &[
+                (
+                    "O",
+                    "0ba3835f88f90388e74e54584125ce142be0de24c6b0d37746e075b891756671",
+                ),
+                (
+                    "U",
+                    "ad944bba9714b0f0d9da179955e19450c139eca62ecef0863aefbaf01726373f",
+                ),
+            ],
+        );
+        check(
+            &encrypt,
+            "user",
+            "f39f55714572d7d71ba77d681118d8b6",
+            "758e8fde0eec1d1890965604db5c",
+        );
+    }
+
+    #[test]
+    fn revision_4_aes() {
+        let mut encrypt = encrypt(
+            &[("V", 4), ("R", 4), ("Length", 128)],
+            Some("AESV2"),
+            &[
+                (
+                    "O",
+                    "0ba3835f88f90388e74e54584125ce142be0de24c6b0d37746e075b891756671",
+                ),
+                (
+                    "U",
+                    "1047ef7d98aec87125ce8a7cefcd6a6eb1ce6745949931bc05817a414606804a",
+                ),
+            ],
+        );
+        // Leaving metadata unencrypted changes the key
+        encrypt.insert(Name::from("EncryptMetadata"), Object::Bool(false));
+        check(
+            &encrypt,
+            "user",
+            "f9cd7fa9d37505201b27d6ab534159e4",
+            "8f50ebbbfc9b9712322e90a8fd422273b2d3b32c3def589047a0533d724dbf0e",
+        );
+
+        let handler = SecurityHandler::new(&encrypt, &hex(FILE_ID), b"user").unwrap();
+        let metadata =
+            Dict::from_iter([(Name::from("Type"), Object::Name(Name::from("Metadata")))]);
+        let id = ObjectId { num: 7, gen: 0 };
+        let data = handler
+            .decrypt_stream(id, &metadata, Cow::Borrowed(b"<x:xmpmeta/>"))
+            .unwrap();
+        assert_eq!(data, &b"<x:xmpmeta/>"[..]);
+    }
+
+    #[test]
+    fn revision_5() {
+        let encrypt = encrypt(
+            &[("V", 5), ("R", 5), ("Length", 256)],
+            Some("AESV3"),
+            &[
+                (
+                    "O",
+                    "cfe767cd77a406c7ea36c23b67a51f4e97a14d55fa255b0b5dffe0d0eed82dd2\
+                     e748c6e698f7ce508007a85b4e4fcfce",
+                ),
+                (
+                    "U",
+                    "1f4111ecdd94e0a9606665500cd450ab41bc5c50d12c84fd7923826de9552d62\
+                     2a6f4f1e34d07543d6f72739b2a8edde",
+                ),
+                (
+                    "OE",
+                    "d764a1f5c83daa520ebf3190980e008db66f11fe17493095ef5a139c48309b3d",
+                ),
+                (
+                    "UE",
+                    "dc7d7d2a1a72c0a1acd5436fe7c36a1559727aa34863108e989afbcff988946f",
+                ),
+                ("Perms", "5944ad552439290b400b1c04875d573c"),
+            ],
+        );
+        check(
+            &encrypt,
+            "user",
+            "b29e904dca4c7477f8e629afdc4271df62678c650fb5d064bf530bc7f730afe8",
+            "5be0545cdb60e5cd4a7dfaa107bab4a1a8e3a313ad501c9125fde42cd03fd204",
+        );
+    }
+
+    #[test]
+    fn revision_6() {
+        let encrypt = encrypt(
+            &[("V", 5), ("R", 6), ("Length", 256)],
+            Some("AESV3"),
+            &[
+                (
+                    "O",
+                    "8d73e65551677e885fd465aef035b1142ba66b3113fb5feda5f200fc63ea0291\
+                     926a4698e9f8495681141d07b01e4d15",
+                ),
+                (
+                    "U",
+                    "15aac736cfc0d8e95f48d73be19aed0996c82b7a7ca0f0b7a4016c1f8674c9ee\
+                     5d07b7020ab3d5a6a560e7119ca77e1f",
+                ),
+                (
+                    "OE",
+                    "2bc965a52041b293b01f4cee954ed90942d9d80ee6e012c1f85413e23dd511ec",
+                ),
+                (
+                    "UE",
+                    "ea81570ff2ec2ff98f10bfcab7aaee4d4f0ddca8852449ce7703fc98897eeb9d",
+                ),
+                ("Perms", "881919acf783a50985df6256b0f1498c"),
+            ],
+        );
+        check(
+            &encrypt,
+            "\u{fc}ser",
+            "9d2f2a84fff85226cc9a3a294923248d69c159aa97d7fba914077c46780c3a2a",
+            "e4fa54343ceda4da632d5efa96dfc26144307ab58abc5c558879da8a9b7a739a",
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! AES (FIPS 197) with 128 and 256 bit keys, in CBC mode. Decryption is what the security
+//! handler needs; encryption is only used by the revision 6 key derivation.
+
+/// The S-box, built by walking the multiplicative group of GF(2^8) instead of being listed.
+const SBOX: [u8; 256] = {
+    let mut sbox = [0x63; 256];
+    // `p` runs through the powers of 3, and `q` through their inverses
+    let (mut p, mut q) = (1u8, 1u8);
+    loop {
+        p ^= (p << 1) ^ if p & 0x80 != 0 { 0x1B } else { 0 };
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09;
+        }
+        let affine = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+        sbox[p as usize] = affine ^ 0x63;
+        if p == 1 {
+            break;
+        }
+    }
+    sbox
+};
+
+const INV_SBOX: [u8; 256] = {
+    let mut inv = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[SBOX[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+};
+
+/// Multiplication by 2 in GF(2^8).
+fn xtime(a: u8) -> u8 {
+    (a << 1) ^ if a & 0x80 != 0 { 0x1B } else { 0 }
+}
+
+/// Multiplication in GF(2^8), looping over the bits of `b`.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+pub struct Aes {
+    round_keys: Vec<[u8; 16]>,
+}
+
+impl Aes {
+    /// Expands a 16, 24 or 32 byte key.
+    pub fn new(key: &[u8]) -> Self {
+        let nk = key.len() / 4;
+        let rounds = nk + 6;
+        let mut words: Vec<[u8; 4]> = key
+            .chunks_exact(4)
+            .map(|word| word.try_into().unwrap())
+            .collect();
+        let mut rcon = 1u8;
+        for i in nk..4 * (rounds + 1) {
+            let mut word = words[i - 1];
+            if i % nk == 0 {
+                word.rotate_left(1);
+                word = word.map(|byte| SBOX[byte as usize]);
+                word[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if nk > 6 && i % nk == 4 {
+                word = word.map(|byte| SBOX[byte as usize]);
+            }
+            let earlier = words[i - nk];
+            words.push(std::array::from_fn(|j| word[j] ^ earlier[j]));
+        }
+
+        Self {
+            round_keys: words
+                .chunks_exact(4)
+                .map(|round| std::array::from_fn(|i| round[i / 4][i % 4]))
+                .collect(),
+        }
+    }
+
+    fn add_round_key(block: &mut [u8; 16], key: &[u8; 16]) {
+        for (byte, key) in block.iter_mut().zip(key) {
+            *byte ^= key;
+        }
+    }
+
+    /// The state is stored a column at a time, so row `r` of column `c` is at `4 * c + r`.
+    fn shift_rows(block: &mut [u8; 16], inverse: bool) {
+        let old = *block;
+        for c in 0..4 {
+            for r in 0..4 {
+                let from = (if inverse { c + 4 - r } else { c + r }) % 4;
+                block[4 * c + r] = old[4 * from + r];
+            }
+        }
+    }
+
+    /// Multiplies each column by 2, 3, 1, 1, written out since revision 6's password hash
+    /// spends nearly all its time here.
+    fn mix_columns(block: &mut [u8; 16]) {
+        for column in block.chunks_exact_mut(4) {
+            let old = [column[0], column[1], column[2], column[3]];
+            let all = old[0] ^ old[1] ^ old[2] ^ old[3];
+            for r in 0..4 {
+                column[r] = old[r] ^ all ^ xtime(old[r] ^ old[(r + 1) % 4]);
+            }
+        }
+    }
+
+    fn inv_mix_columns(block: &mut [u8; 16]) {
+        const COEFFICIENTS: [u8; 4] = [14, 11, 13, 9];
+        for column in block.chunks_exact_mut(4) {
+            let old: [u8; 4] = (*column).try_into().unwrap();
+            for (r, byte) in column.iter_mut().enumerate() {
+                *byte = (0..4).fold(0, |acc, i| acc ^ mul(old[(r + i) % 4], COEFFICIENTS[i]));
+            }
+        }
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let rounds = self.round_keys.len() - 1;
+        Self::add_round_key(block, &self.round_keys[0]);
+        for round in 1..=rounds {
+            *block = block.map(|byte| SBOX[byte as usize]);
+            Self::shift_rows(block, false);
+            if round != rounds {
+                Self::mix_columns(block);
+            }
+            Self::add_round_key(block, &self.round_keys[round]);
+        }
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8; 16]) {
+        let rounds = self.round_keys.len() - 1;
+        Self::add_round_key(block, &self.round_keys[rounds]);
+        for round in (0..rounds).rev() {
+            Self::shift_rows(block, true);
+            *block = block.map(|byte| INV_SBOX[byte as usize]);
+            Self::add_round_key(block, &self.round_keys[round]);
+            if round != 0 {
+                Self::inv_mix_columns(block);
+            }
+        }
+    }
+}
+
+/// Decrypts whole blocks of CBC-mode `data`. Padding is left for the caller to remove.
+pub fn cbc_decrypt(key: &[u8], iv: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    let aes = Aes::new(key);
+    let mut previous = *iv;
+    let mut out = Vec::with_capacity(data.len());
+    for chunk in data.chunks_exact(16) {
+        let mut block: [u8; 16] = chunk.try_into().unwrap();
+        aes.decrypt_block(&mut block);
+        out.extend(block.iter().zip(&previous).map(|(byte, prev)| byte ^ prev));
+        previous = chunk.try_into().unwrap();
+    }
+    out
+}
+
+/// Encrypts whole blocks of `data` in CBC mode, without padding.
+pub fn cbc_encrypt(key: &[u8], iv: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    let aes = Aes::new(key);
+    let mut previous = *iv;
+    let mut out = Vec::with_capacity(data.len());
+    for chunk in data.chunks_exact(16) {
+        let mut block: [u8; 16] = std::array::from_fn(|i| chunk[i] ^ previous[i]);
+        aes.encrypt_block(&mut block);
+        out.extend_from_slice(&block);
+        previous = block;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+
+    /// Encrypts and decrypts a block with `key`, checking it against `ciphertext`.
+    fn check(key: &str, plaintext: &str, ciphertext: &str) {
+        let aes = Aes::new(&hex(key));
+        let mut block: [u8; 16] = hex(plaintext).try_into().unwrap();
+        aes.encrypt_block(&mut block);
+        assert_eq!(block.to_vec(), hex(ciphertext));
+        aes.decrypt_block(&mut block);
+        assert_eq!(block.to_vec(), hex(plaintext));
+    }
+
+    // FIPS-197, appendix B
+    #[test]
+    fn cipher_example() {
+        check(
+            "2b7e151628aed2a6abf7158809cf4f3c",
+            "3243f6a8885a308d313198a2e0370734",
+            "3925841d02dc09fbdc118597196a0b32",
+        );
+    }
+
+    // FIPS-197, appendix C
+    #[test]
+    fn aes_128() {
+        check(
+            "000102030405060708090a0b0c0d0e0f",
+            "00112233445566778899aabbccddeeff",
+            "69c4e0d86a7b0430d8cdb78070b4c55a",
+        );
+    }
+
+    #[test]
+    fn aes_192() {
+        check(
+            "000102030405060708090a0b0c0d0e0f1011121314151617",
+            "00112233445566778899aabbccddeeff",
+            "dda97ca4864cdfe06eaf70a0ec0d7191",
+        );
+    }
+
+    #[test]
+    fn aes_256() {
+        check(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            "00112233445566778899aabbccddeeff",
+            "8ea2b7ca516745bfeafc49904b496089",
+        );
+    }
+
+    // NIST SP 800-38A, F.2.1 and F.2.2 (the first two blocks)
+    #[test]
+    fn cbc() {
+        let key = hex("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv: [u8; 16] = hex("000102030405060708090a0b0c0d0e0f").try_into().unwrap();
+        let plaintext = hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+        let ciphertext = hex("7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2");
+        assert_eq!(cbc_encrypt(&key, &iv, &plaintext), ciphertext);
+        assert_eq!(cbc_decrypt(&key, &iv, &ciphertext), plaintext);
+    }
+}
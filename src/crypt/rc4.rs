@@ -0,0 +1,59 @@
+//! The RC4 stream cipher, used by security handler revisions 2 to 4. Encrypting and decrypting
+//! are the same operation.
+
+pub fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: [u8; 256] = std::array::from_fn(|i| i as u8);
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|&byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            let k = state[state[i as usize].wrapping_add(state[j as usize]) as usize];
+            byte ^ k
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+
+    // RFC 6229: the keystream at offsets 0, 16 and 1536, which encrypting zeros gives
+    #[test]
+    fn keystream() {
+        let vectors = [
+            (
+                "0102030405",
+                "b2396305f03dc027ccc3524a0a1118a8",
+                "6982944f18fc82d589c403a47a0d0919",
+                "d8729db41882259bee4f825325f5a130",
+            ),
+            (
+                "0102030405060708",
+                "97ab8a1bf0afb96132f2f67258da15a8",
+                "8263efdb45c4a18684ef87e6b19e5b09",
+                "8369e1a965610be887fbd0c79162aafb",
+            ),
+            (
+                "0102030405060708090a0b0c0d0e0f10",
+                "9ac7cc9a609d1ef7b2932899cde41b97",
+                "5248c4959014126a6e8a84f11d1a9e1c",
+                "ffa0b514647ec04f6306b892ae661181",
+            ),
+        ];
+        for (key, at_0, at_16, at_1536) in vectors {
+            let stream = rc4(&hex(key), &[0; 1552]);
+            assert_eq!(stream[..16], hex(at_0), "key {}", key);
+            assert_eq!(stream[16..32], hex(at_16), "key {}", key);
+            assert_eq!(stream[1536..], hex(at_1536), "key {}", key);
+        }
+    }
+}
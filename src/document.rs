@@ -0,0 +1,368 @@
+//! The document as a whole, and what's in it.
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek},
+    path::Path,
+    sync::Arc,
+};
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+use crate::source::Mapping;
+use crate::{
+    crypt,
+    filter::{self, Codec, ImageInfo},
+    object::{Dict, Object, ObjectId},
+    parser::{ParseError, Parser, Revision, Version},
+    source::{LazyFile, Source},
+};
+
+/// Settings for opening a file.
+#[derive(Clone, Debug, Default)]
+pub struct Options<'p> {
+    /// The user or owner password of an encrypted file. Most encrypted files have an empty
+    /// user password.
+    pub password: &'p [u8],
+    /// Whether to keep comments, for tools that write the file back out. See
+    /// [`Document::comments`].
+    pub keep_comments: bool,
+    /// The most bytes a stream's filters may decode it to, [`filter::MAX_DECODED_LEN`] if
+    /// `None`. Streams that decode to more are treated as corrupt.
+    pub max_decoded_len: Option<usize>,
+}
+
+/// The JPEG or JPEG 2000 data of an image XObject. freed doesn't decode these images, but
+/// reads their headers.
+#[derive(Clone, Debug)]
+pub struct EncodedImage<'a> {
+    pub codec: Codec,
+    /// The whole JPEG or JPEG 2000 file, with any other filters undone.
+    pub data: Cow<'a, [u8]>,
+    /// What the headers say about the image, or why they couldn't be read.
+    pub info: Result<ImageInfo, filter::FilterError>,
+    /// Where the image dictionary disagrees with the headers.
+    pub mismatches: Vec<String>,
+}
+
+/// A parsed PDF file. Objects are parsed the first time they're asked for.
+#[derive(Clone, Debug)]
+pub struct Document<'a> {
+    pub(crate) parser: Parser<'a>,
+}
+
+impl Document<'static> {
+    /// Opens the file at `path`. Only the parts of it that are needed are read in, as
+    /// they're needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ParseError> {
+        Self::open_with_options(path, &Options::default())
+    }
+
+    pub fn open_with_options(
+        path: impl AsRef<Path>,
+        options: &Options,
+    ) -> Result<Self, ParseError> {
+        Self::from_reader_with_options(File::open(path)?, options)
+    }
+
+    /// Parses the file that `reader` reads. Only the parts of it that are needed (the
+    /// trailer, the xref sections, and whichever objects are asked for) are read in, a chunk
+    /// at a time, so large files don't have to fit in memory.
+    pub fn from_reader(reader: impl Read + Seek + Send + 'static) -> Result<Self, ParseError> {
+        Self::from_reader_with_options(reader, &Options::default())
+    }
+
+    pub fn from_reader_with_options(
+        reader: impl Read + Seek + Send + 'static,
+        options: &Options,
+    ) -> Result<Self, ParseError> {
+        let file = LazyFile::new(reader)?;
+        Ok(Self {
+            parser: Parser::new(Source::Lazy(Arc::new(file)), options)?,
+        })
+    }
+
+    /// Maps the file at `path` into memory, and parses it. The operating system reads in
+    /// whatever parts of the file are used. Where there's no `mmap`, this is the same as
+    /// [`Document::open`].
+    ///
+    /// # Safety
+    ///
+    /// The file mustn't be changed while the document is around. Nothing stops another
+    /// process changing it, and the document's view of it would change too.
+    pub unsafe fn map(path: impl AsRef<Path>) -> Result<Self, ParseError> {
+        Self::map_with_options(path, &Options::default())
+    }
+
+    /// # Safety
+    ///
+    /// The same as for [`Document::map`].
+    pub unsafe fn map_with_options(
+        path: impl AsRef<Path>,
+        options: &Options,
+    ) -> Result<Self, ParseError> {
+        let file = File::open(path)?;
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        let source = Source::Mapped(Arc::new(Mapping::new(&file)?));
+        #[cfg(not(all(unix, target_pointer_width = "64")))]
+        let source = Source::Lazy(Arc::new(LazyFile::new(file)?));
+        Ok(Self {
+            parser: Parser::new(source, options)?,
+        })
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Parses the file `data`. The document borrows from it rather than copying it.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_with_options(data, &Options::default())
+    }
+
+    pub fn from_bytes_with_options(data: &'a [u8], options: &Options) -> Result<Self, ParseError> {
+        Ok(Self {
+            parser: Parser::new(Source::Borrowed(data), options)?,
+        })
+    }
+
+    /// The version in the file's header.
+    pub fn version(&self) -> Version {
+        self.parser.version
+    }
+
+    /// The trailer dictionary of the latest revision, or one made up for a damaged file.
+    pub fn trailer(&self) -> &Dict<'a> {
+        &self.parser.trailer_dict
+    }
+
+    /// The document catalog, which the trailer's `/Root` refers to.
+    pub fn catalog(&mut self) -> Result<&Dict<'a>, ParseError> {
+        let id = match self.parser.trailer_dict.get("Root") {
+            Some(&Object::Reference(id)) => self.follow(id)?,
+            _ => {
+                return Err(ParseError::InvalidStructure(
+                    "trailer has no `/Root` reference".to_owned(),
+                ))
+            }
+        };
+        match self.get_object(id)? {
+            Object::Dict(catalog) => Ok(catalog),
+            _ => Err(ParseError::InvalidStructure(format!(
+                "catalog {} isn't a dictionary",
+                id
+            ))),
+        }
+    }
+
+    /// The object with the given id, or the null object if there's no such object.
+    pub fn get_object(&mut self, id: ObjectId) -> Result<&Object<'a>, ParseError> {
+        self.parser.resolve(id)
+    }
+
+    /// The ids of every object in the file, in order.
+    pub fn object_ids(&self) -> Vec<ObjectId> {
+        self.parser.object_ids()
+    }
+
+    /// Every object in the file, in order, along with its id. Objects that can't be parsed
+    /// come with the reason why instead.
+    pub fn objects(
+        &mut self,
+    ) -> impl Iterator<Item = (ObjectId, Result<Object<'a>, ParseError>)> + '_ {
+        self.object_ids()
+            .into_iter()
+            .map(|id| (id, self.get_object(id).cloned()))
+    }
+
+    /// Every xref section in the file, oldest first. Later ones are incremental updates.
+    /// Empty if the file was damaged, and its objects found by scanning it instead.
+    pub fn revisions(&self) -> &[Revision<'a>] {
+        &self.parser.revisions
+    }
+
+    /// The deleted objects, as (object number, generation number to reuse) pairs.
+    pub fn free_objects(&self) -> Vec<(usize, u16)> {
+        self.parser.free_objects()
+    }
+
+    /// The object number and generation number a new object should get.
+    pub fn next_free_object(&self) -> (usize, u16) {
+        self.parser.next_free_object()
+    }
+
+    /// If the file was too damaged to read normally, what was wrong with it. Its objects
+    /// were found by scanning the whole file instead.
+    pub fn recovered_from(&self) -> Option<&ParseError> {
+        self.parser.recovered_from.as_ref()
+    }
+
+    /// The comments found so far, as (offset, text) pairs in the order they're in the file.
+    /// The text leaves out the `%` and the end of the line. Only kept if the document was
+    /// opened with [`Options::keep_comments`].
+    pub fn comments(&self) -> impl Iterator<Item = (usize, &[u8])> + '_ {
+        self.parser
+            .comments
+            .iter()
+            .map(|(&offset, text)| (offset, &**text))
+    }
+
+    /// How the file is encrypted, or `None` if it isn't.
+    pub fn security(&self) -> Option<&crypt::SecurityHandler> {
+        self.parser.security.as_ref()
+    }
+
+    /// `obj` itself, or if it's a reference, the object it (eventually) refers to.
+    pub fn deref<'b>(&'b mut self, obj: &'b Object<'a>) -> Result<&'b Object<'a>, ParseError> {
+        match *obj {
+            Object::Reference(id) => {
+                let id = self.follow(id)?;
+                self.get_object(id)
+            }
+            _ => Ok(obj),
+        }
+    }
+
+    /// The id of the object that `id` (eventually) refers to, which isn't a reference.
+    pub(crate) fn follow(&mut self, mut id: ObjectId) -> Result<ObjectId, ParseError> {
+        let mut visited = HashSet::new();
+        while visited.insert(id) {
+            match self.get_object(id)? {
+                Object::Reference(next) => id = *next,
+                _ => return Ok(id),
+            }
+        }
+        Err(ParseError::ReferenceCycle { id })
+    }
+
+    /// The integer `key` of `dict`, following a reference if need be.
+    fn int_entry(&mut self, dict: &Dict<'a>, key: &str) -> Result<Option<i64>, ParseError> {
+        match dict.get(key) {
+            Some(value) => Ok(self.deref(value)?.as_int()),
+            None => Ok(None),
+        }
+    }
+
+    /// How many components a colour in the colour space `space` has, if it's known.
+    fn colour_components(&mut self, space: &Object<'a>) -> Result<Option<i64>, ParseError> {
+        let space = self.deref(space)?.clone();
+        let components = match &space {
+            Object::Name(name) => match name.as_str() {
+                Some("DeviceGray" | "G") => 1,
+                Some("DeviceRGB" | "RGB") => 3,
+                Some("DeviceCMYK" | "CMYK") => 4,
+                _ => return Ok(None),
+            },
+            Object::Array(array) => match array.first().and_then(Object::as_name) {
+                Some("CalGray" | "Indexed" | "I" | "Separation") => 1,
+                Some("CalRGB" | "Lab") => 3,
+                // The ICC profile stream says how many components it has
+                Some("ICCBased") => match array.get(1).map(|profile| self.deref(profile)) {
+                    Some(Ok(Object::Stream { dict, .. })) => {
+                        let dict = dict.clone();
+                        return self.int_entry(&dict, "N");
+                    }
+                    Some(Err(err)) => return Err(err),
+                    _ => return Ok(None),
+                },
+                // One component per colourant name
+                Some("DeviceN") => match array.get(1).map(|names| self.deref(names)) {
+                    Some(Ok(Object::Array(names))) => names.len() as i64,
+                    Some(Err(err)) => return Err(err),
+                    _ => return Ok(None),
+                },
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(components))
+    }
+
+    /// The data of image XObject `id` if it's a JPEG or JPEG 2000 image, checked against the
+    /// image dictionary, or `None` if `id` isn't such an image.
+    pub fn encoded_image(&mut self, id: ObjectId) -> Result<Option<EncodedImage<'a>>, ParseError> {
+        let Object::Stream {
+            dict,
+            data,
+            decoded,
+        } = self.get_object(id)?
+        else {
+            return Ok(None);
+        };
+        if dict.get("Subtype").and_then(Object::as_name) != Some("Image") {
+            return Ok(None);
+        }
+        let (dict, data, decoded) = (dict.clone(), data.clone(), decoded.clone());
+
+        // The image codec comes last, after any filters applied on top of it
+        let filter = match dict.get("Filter") {
+            Some(filter) => self.deref(filter)?.clone(),
+            None => return Ok(None),
+        };
+        let filter = match filter {
+            Object::Array(filters) => match filters.last() {
+                Some(filter) => self.deref(filter)?.clone(),
+                None => return Ok(None),
+            },
+            filter => filter,
+        };
+        let Some(codec) = filter.as_name().and_then(Codec::from_filter) else {
+            return Ok(None);
+        };
+        let data = match decoded {
+            Some(decoded) => decoded,
+            None => self.parser.stream_contents(id, &dict, data)?,
+        };
+
+        let info = codec.probe(&data);
+        let mut mismatches = Vec::new();
+        if let Ok(info) = &info {
+            let format = match codec {
+                Codec::Jpeg => "JPEG",
+                Codec::Jpeg2000 => "JPEG 2000",
+            };
+            let mut check = |key: &str, value: Option<i64>, actual: i64| match value {
+                Some(value) if value != actual => mismatches.push(format!(
+                    "`/{}` is {}, but the {} data says {}",
+                    key, value, format, actual
+                )),
+                _ => {}
+            };
+            check("Width", self.int_entry(&dict, "Width")?, info.width as i64);
+            check(
+                "Height",
+                self.int_entry(&dict, "Height")?,
+                info.height as i64,
+            );
+            // JPEG 2000 images ignore `/BitsPerComponent`
+            if codec == Codec::Jpeg {
+                let bits = self.int_entry(&dict, "BitsPerComponent")?;
+                check("BitsPerComponent", bits, info.bits_per_component as i64);
+            }
+
+            let components = match dict.get("ColorSpace") {
+                Some(space) => self.colour_components(space)?,
+                None => None,
+            };
+            // JPEG 2000 data can have an extra component for the soft mask
+            let alpha = match self.int_entry(&dict, "SMaskInData")? {
+                Some(smask) if smask != 0 && codec == Codec::Jpeg2000 => 1,
+                _ => 0,
+            };
+            if let Some(components) = components {
+                if components + alpha != info.components as i64 {
+                    mismatches.push(format!(
+                        "`/ColorSpace` has {} components, but the {} data has {}",
+                        components, format, info.components
+                    ));
+                }
+            }
+        }
+
+        Ok(Some(EncodedImage {
+            codec,
+            data,
+            info,
+            mismatches,
+        }))
+    }
+}
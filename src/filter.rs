@@ -0,0 +1,158 @@
+//! Stream filters, which undo whatever compression or encoding was applied to stream data.
+
+mod ascii;
+mod ccitt;
+mod dct;
+mod flate;
+mod jbig2;
+mod jpx;
+mod lzw;
+mod predictor;
+mod run_length;
+
+use core::fmt;
+
+use crate::{Dict, Object};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterError {
+    /// A filter (or filter parameter) this parser doesn't implement.
+    Unsupported(String),
+    /// The data can't be decoded with the filter it claims to use.
+    Corrupt {
+        filter: &'static str,
+        message: String,
+    },
+    /// The data decodes to more than the limit it was given, which is more likely a
+    /// decompression bomb than a real image or page.
+    TooLarge { filter: &'static str, limit: usize },
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::Unsupported(what) => write!(f, "unsupported filter: {}", what),
+            FilterError::Corrupt { filter, message } => {
+                write!(f, "corrupt {} data: {}", filter, message)
+            }
+            FilterError::TooLarge { filter, limit } => {
+                write!(f, "{} data decodes to more than {} bytes", filter, limit)
+            }
+        }
+    }
+}
+
+/// The most bytes a filter decodes a stream to, unless [`Options::max_decoded_len`] says
+/// otherwise. It's enough for an uncompressed 8 bit RGB image of about 6000x7000 pixels.
+///
+/// [`Options::max_decoded_len`]: crate::Options::max_decoded_len
+pub const MAX_DECODED_LEN: usize = 1 << 27;
+
+/// Decodes `data` with the filter called `name` (or its inline image abbreviation), given
+/// that filter's `/DecodeParms`. Data that would decode to more than `limit` bytes is
+/// rejected with [`FilterError::TooLarge`].
+pub fn decode(
+    name: &str,
+    data: &[u8],
+    parms: Option<&Dict>,
+    limit: usize,
+) -> Result<Vec<u8>, FilterError> {
+    match name {
+        "FlateDecode" | "Fl" => predictor::unpredict(flate::inflate(data, limit)?, parms),
+        "LZWDecode" | "LZW" => {
+            let early_change = int_parm(parms, "EarlyChange", 1) != 0;
+            predictor::unpredict(lzw::decode(data, early_change, limit)?, parms)
+        }
+        "ASCIIHexDecode" | "AHx" => ascii::decode_hex(data),
+        "ASCII85Decode" | "A85" => ascii::decode_85(data),
+        "RunLengthDecode" | "RL" => run_length::decode(data, limit),
+        "CCITTFaxDecode" | "CCF" => ccitt::decode(data, parms, limit),
+        "JBIG2Decode" => jbig2::decode(data, parms, limit),
+        // Whole JPEG and JPEG 2000 files, which are left as they are
+        "DCTDecode" | "DCT" | "JPXDecode" => Ok(data.to_vec()),
+        // Streams are decrypted before their filters are applied
+        "Crypt" => Ok(data.to_vec()),
+        _ => Err(FilterError::Unsupported(name.to_owned())),
+    }
+}
+
+/// An image format whose data is left encoded: the stream's data is a file in that format.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Jpeg,
+    Jpeg2000,
+}
+
+/// An image's size and format, as its encoded data describes it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub components: u16,
+    pub bits_per_component: u8,
+}
+
+impl Codec {
+    /// The codec for the filter called `name`, if it's one that leaves the data encoded.
+    pub fn from_filter(name: &str) -> Option<Self> {
+        match name {
+            "DCTDecode" | "DCT" => Some(Codec::Jpeg),
+            "JPXDecode" => Some(Codec::Jpeg2000),
+            _ => None,
+        }
+    }
+
+    /// Reads the image's size and format from the headers in `data`.
+    pub fn probe(self, data: &[u8]) -> Result<ImageInfo, FilterError> {
+        match self {
+            Codec::Jpeg => dct::probe(data),
+            Codec::Jpeg2000 => jpx::probe(data),
+        }
+    }
+
+    /// The usual file extension for `data`. JPEG 2000 data is either a JP2 file or just a
+    /// codestream.
+    pub fn extension(self, data: &[u8]) -> &'static str {
+        match self {
+            Codec::Jpeg => "jpg",
+            Codec::Jpeg2000 if jpx::is_jp2(data) => "jp2",
+            Codec::Jpeg2000 => "j2k",
+        }
+    }
+}
+
+fn corrupt(filter: &'static str, message: impl Into<String>) -> FilterError {
+    FilterError::Corrupt {
+        filter,
+        message: message.into(),
+    }
+}
+
+fn too_large(filter: &'static str, limit: usize) -> FilterError {
+    FilterError::TooLarge { filter, limit }
+}
+
+/// An integer entry of a `/DecodeParms` dictionary, or `default` if it's missing.
+fn int_parm(parms: Option<&Dict>, key: &str, default: i64) -> i64 {
+    parms
+        .and_then(|parms| parms.get(key))
+        .and_then(Object::as_int)
+        .unwrap_or(default)
+}
+
+/// A boolean entry of a `/DecodeParms` dictionary, or `default` if it's missing.
+fn bool_parm(parms: Option<&Dict>, key: &str, default: bool) -> bool {
+    parms
+        .and_then(|parms| parms.get(key))
+        .and_then(Object::as_bool)
+        .unwrap_or(default)
+}
+
+/// A `/DecodeParms` dictionary of integer entries, for tests.
+#[cfg(test)]
+fn parms(entries: &[(&'static str, i64)]) -> Dict<'static> {
+    entries
+        .iter()
+        .map(|&(key, value)| (crate::Name::from(key), Object::Int(value)))
+        .collect()
+}
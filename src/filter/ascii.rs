@@ -0,0 +1,122 @@
+//! `/ASCIIHexDecode` and `/ASCII85Decode`, which encode binary data as printable text.
+
+use super::{corrupt, FilterError};
+use crate::parser::Parser;
+
+/// Decodes pairs of hex digits up to the `>` end-of-data marker, ignoring whitespace. A
+/// final odd digit is treated as if it were followed by a `0`.
+pub fn decode_hex(data: &[u8]) -> Result<Vec<u8>, FilterError> {
+    let mut out = Vec::with_capacity(data.len() / 2);
+    let mut high = None;
+
+    for &byte in data {
+        let digit = match byte {
+            b'>' => break,
+            b'0'..=b'9' => byte - b'0',
+            b'a'..=b'f' => byte - b'a' + 10,
+            b'A'..=b'F' => byte - b'A' + 10,
+            _ if Parser::is_ascii_whitespace(byte) => continue,
+            _ => {
+                return Err(corrupt(
+                    "ASCIIHexDecode",
+                    format!("invalid hex digit {:?}", byte as char),
+                ));
+            }
+        };
+        match high.take() {
+            Some(high) => out.push(high << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+    if let Some(high) = high {
+        out.push(high << 4);
+    }
+
+    Ok(out)
+}
+
+/// Decodes base-85 groups of five characters (`!` to `u`) into four bytes each, up to the
+/// `~>` end-of-data marker. `z` stands for a whole group of zero bytes.
+pub fn decode_85(data: &[u8]) -> Result<Vec<u8>, FilterError> {
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    let mut out = Vec::with_capacity(data.len() / 5 * 4);
+    let mut group = [0u8; 5];
+    let mut n = 0;
+
+    for &byte in data {
+        match byte {
+            b'~' => break,
+            b'z' if n == 0 => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group[n] = byte - b'!';
+                n += 1;
+                if n == 5 {
+                    out.extend_from_slice(&group_value(&group)?.to_be_bytes());
+                    n = 0;
+                }
+            }
+            _ if Parser::is_ascii_whitespace(byte) => continue,
+            _ => {
+                return Err(corrupt(
+                    "ASCII85Decode",
+                    format!("invalid character {:?}", byte as char),
+                ));
+            }
+        }
+    }
+
+    // A final partial group of n characters is padded with `u`s and gives n - 1 bytes
+    match n {
+        0 => {}
+        1 => {
+            return Err(corrupt(
+                "ASCII85Decode",
+                "final group has only one character",
+            ))
+        }
+        _ => {
+            group[n..].fill(b'u' - b'!');
+            out.extend_from_slice(&group_value(&group)?.to_be_bytes()[..n - 1]);
+        }
+    }
+
+    Ok(out)
+}
+
+fn group_value(group: &[u8; 5]) -> Result<u32, FilterError> {
+    let value = group
+        .iter()
+        .fold(0u64, |value, &digit| value * 85 + digit as u64);
+    u32::try_from(value).map_err(|_| corrupt("ASCII85Decode", "group value is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        assert_eq!(decode_hex(b"48 65\n6C6c 6f>").unwrap(), b"Hello");
+        // A final odd digit, and anything after the end-of-data marker
+        assert_eq!(decode_hex(b"7A7>0g").unwrap(), b"zp");
+        assert!(decode_hex(b"4g>").is_err());
+    }
+
+    #[test]
+    fn base_85() {
+        assert_eq!(
+            decode_85(b"<~9jqo^BlbD-BleB1DJ+*+F(f,q~>").unwrap(),
+            b"Man is distinguished"
+        );
+        // `z` for four zero bytes, and a final partial group
+        assert_eq!(decode_85(b"z@:E^H!!!!~>").unwrap(), b"\0\0\0\0abc\0\0\0\0");
+        // Whitespace is skipped, and data ends at `~` even without the `>`
+        assert_eq!(decode_85(b"BOu!r\nDZ~").unwrap(), b"hello");
+        assert_eq!(decode_85(b"BOu!rDZ~>BOu!rDZ").unwrap(), b"hello");
+
+        // `z` in the middle of a group, a lone final character, and groups past 2^32
+        assert!(decode_85(b"BOzu!rDZ~>").is_err());
+        assert!(decode_85(b"BOu!rD~>").is_err());
+        assert!(decode_85(b"uuuuu~>").is_err());
+    }
+}
@@ -0,0 +1,657 @@
+//! `/CCITTFaxDecode`: bilevel images compressed with the ITU-T T.4 (Group 3) and T.6 (Group 4)
+//! fax codings.
+//!
+//! Every row is stored as the positions where the colour changes (its "changing elements"),
+//! which is what the two-dimensional coding describes rows in terms of anyway.
+
+use super::{bool_parm, corrupt, int_parm, too_large, FilterError};
+use crate::Dict;
+
+/// Longest code in any of the tables below.
+const MAX_CODE_LEN: u32 = 13;
+
+enum CcittError {
+    /// Ran out of input in the middle of a row.
+    Eof,
+    Corrupt(&'static str),
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Pass,
+    Horizontal,
+    /// `a1` is this many pixels to the right of `b1` (or to the left, if negative).
+    Vertical(isize),
+    /// Uncompressed mode and the like, which nothing produces in practice.
+    Extension,
+}
+
+/// How a fax stream is coded: the parameters from its `/DecodeParms`.
+pub struct Options {
+    pub k: i64,
+    pub columns: usize,
+    pub rows: usize,
+    pub byte_align: bool,
+    pub end_of_block: bool,
+    pub black_is_1: bool,
+    /// The most bytes of rows to decode.
+    pub limit: usize,
+}
+
+/// Decodes fax data into rows of packed pixels, one bit each, with every row padded to a whole
+/// byte. Without `/BlackIs1`, black pixels are 0 bits, as in a `/DecodeParms`-less image.
+pub fn decode(data: &[u8], parms: Option<&Dict>, limit: usize) -> Result<Vec<u8>, FilterError> {
+    let columns = int_parm(parms, "Columns", 1728);
+    let rows = int_parm(parms, "Rows", 0);
+    if !(1..=1 << 24).contains(&columns) || rows < 0 {
+        return Err(corrupt(
+            "CCITTFaxDecode",
+            format!("invalid size: {} columns, {} rows", columns, rows),
+        ));
+    }
+
+    decode_with(
+        data,
+        &Options {
+            k: int_parm(parms, "K", 0),
+            columns: columns as usize,
+            rows: rows as usize,
+            byte_align: bool_parm(parms, "EncodedByteAlign", false),
+            end_of_block: bool_parm(parms, "EndOfBlock", true),
+            black_is_1: bool_parm(parms, "BlackIs1", false),
+            limit,
+        },
+    )
+}
+
+/// Like `decode`, with the parameters given directly.
+pub fn decode_with(data: &[u8], options: &Options) -> Result<Vec<u8>, FilterError> {
+    let &Options {
+        k,
+        columns,
+        rows,
+        byte_align,
+        end_of_block,
+        black_is_1,
+        limit,
+    } = options;
+
+    let row_len = columns.div_ceil(8);
+    if row_len.checked_mul(rows).map_or(true, |len| len > limit) {
+        return Err(too_large("CCITTFaxDecode", limit));
+    }
+
+    let white = lookup_table(&WHITE_CODES);
+    let black = lookup_table(&BLACK_CODES);
+    let modes = lookup_table(&MODE_CODES);
+    let mut decoder = Decoder {
+        data,
+        pos: 0,
+        columns,
+        white: &white,
+        black: &black,
+        modes: &modes,
+    };
+
+    let mut out = Vec::new();
+    // The row above the first one is taken to be all white
+    let mut reference = Vec::new();
+    let mut decoded = 0;
+    // Without `/Rows`, rows are decoded until the data runs out. Every row takes at least one
+    // bit, so that's at most one row per bit.
+    while rows == 0 || decoded < rows {
+        // Any row may be preceded by an EOL code (and fill bits before it), followed by a bit
+        // saying whether the row is 2D coded when K > 0. Several EOLs in a row end the data.
+        let row_start = decoder.pos;
+        let mut eols = 0;
+        let mut tag = None;
+        while !decoder.at_end() {
+            // Byte aligned EOLs either start or end on a byte boundary, depending on the
+            // encoder; anything else is just the start of a row with a lot of 0 bits
+            let aligned = decoder.pos % 8 == 0 || (decoder.pos + 12) % 8 == 0;
+            match decoder.peek(12) {
+                1 if aligned || !byte_align => {
+                    decoder.skip(12);
+                    eols += 1;
+                    if k > 0 {
+                        tag = Some(decoder.peek(1));
+                        decoder.skip(1);
+                    }
+                }
+                0 => decoder.skip(1),
+                _ => break,
+            }
+        }
+        if decoder.at_end() || (eols >= 2 && end_of_block) {
+            break;
+        }
+        // With `/EncodedByteAlign`, fill bits make an EOL end on a byte boundary, and rows
+        // without one start on a byte boundary
+        if byte_align && eols == 0 {
+            decoder.pos = row_start;
+            decoder.align();
+        }
+
+        let two_d = match k {
+            ..=-1 => true,
+            0 => false,
+            _ => {
+                let tag = tag.unwrap_or_else(|| {
+                    let tag = decoder.peek(1);
+                    decoder.skip(1);
+                    tag
+                });
+                tag == 0
+            }
+        };
+        let changes = match if two_d {
+            decoder.row_2d(&reference)
+        } else {
+            decoder.row_1d()
+        } {
+            Ok(changes) => changes,
+            // A truncated image keeps the rows it has
+            Err(CcittError::Eof) => break,
+            Err(CcittError::Corrupt(message)) => {
+                return Err(corrupt(
+                    "CCITTFaxDecode",
+                    format!("row {}: {}", decoded, message),
+                ));
+            }
+        };
+
+        let start = out.len();
+        if row_len > limit - start {
+            return Err(too_large("CCITTFaxDecode", limit));
+        }
+        out.resize(start + row_len, if black_is_1 { 0x00 } else { 0xFF });
+        for run in changes.chunks(2) {
+            let end = run.get(1).copied().unwrap_or(columns);
+            for x in run[0]..end {
+                out[start + x / 8] ^= 0x80 >> (x % 8);
+            }
+        }
+        reference = changes;
+        decoded += 1;
+    }
+
+    Ok(out)
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    /// Position in `data`, in bits.
+    pos: usize,
+    columns: usize,
+    white: &'a [Option<(u32, usize)>],
+    black: &'a [Option<(u32, usize)>],
+    modes: &'a [Option<(u32, Mode)>],
+}
+
+impl Decoder<'_> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len() * 8
+    }
+
+    /// The next `n` bits, padded with zeros past the end of the data.
+    fn peek(&self, n: u32) -> u32 {
+        (0..n as usize).fold(0, |value, i| {
+            let bit = self.pos + i;
+            let byte = self.data.get(bit / 8).copied().unwrap_or(0);
+            (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u32
+        })
+    }
+
+    fn skip(&mut self, n: u32) {
+        self.pos += n as usize;
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(8);
+    }
+
+    fn code<T: Copy>(&mut self, table: &[Option<(u32, T)>]) -> Result<T, CcittError> {
+        let (len, value) =
+            table[self.peek(MAX_CODE_LEN) as usize].ok_or(CcittError::Corrupt("invalid code"))?;
+        self.skip(len);
+        if self.pos > self.data.len() * 8 {
+            return Err(CcittError::Eof);
+        }
+        Ok(value)
+    }
+
+    /// A run length: any number of make-up codes (multiples of 64) and a terminating code.
+    fn run(&mut self, white: bool) -> Result<usize, CcittError> {
+        let mut total = 0;
+        loop {
+            let run = self.code(if white { self.white } else { self.black })?;
+            total += run;
+            if run < 64 {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// A row coded as alternating white and black run lengths, starting with white.
+    fn row_1d(&mut self) -> Result<Vec<usize>, CcittError> {
+        let mut changes = Vec::new();
+        let mut pos = 0;
+        let mut white = true;
+        while pos < self.columns {
+            pos = (pos + self.run(white)?).min(self.columns);
+            changes.push(pos);
+            white = !white;
+        }
+        Ok(normalize(changes, self.columns))
+    }
+
+    /// A row coded relative to the changing elements of the row above it, `reference`.
+    fn row_2d(&mut self, reference: &[usize]) -> Result<Vec<usize>, CcittError> {
+        // Padding the reference row makes sure b1 and b2 always exist
+        let reference: Vec<usize> = reference.iter().copied().chain([self.columns; 3]).collect();
+
+        let mut changes = Vec::new();
+        // a0 starts on an imaginary white pixel just before the row
+        let mut a0 = None;
+        let mut white = true;
+        let mut j = 0;
+        loop {
+            let start = a0.unwrap_or(0);
+            if a0.is_some() && start >= self.columns {
+                break;
+            }
+
+            // b1 is the first changing element on the reference row to the right of a0 and
+            // of the opposite colour to it; changes to black are at even indices
+            while a0.is_some_and(|a0| reference[j] <= a0) {
+                j += 1;
+            }
+            let b = if (j % 2 == 0) == white { j } else { j + 1 };
+            let (b1, b2) = (reference[b], reference[b + 1]);
+
+            match self.code(self.modes)? {
+                Mode::Pass => a0 = Some(b2),
+                Mode::Horizontal => {
+                    let a1 = (start + self.run(white)?).min(self.columns);
+                    let a2 = (a1 + self.run(!white)?).min(self.columns);
+                    changes.extend([a1, a2]);
+                    a0 = Some(a2);
+                }
+                Mode::Vertical(delta) => {
+                    let a1 = b1
+                        .checked_add_signed(delta)
+                        .filter(|a1| (start..=self.columns).contains(a1))
+                        .ok_or(CcittError::Corrupt("vertical mode goes outside the row"))?;
+                    changes.push(a1);
+                    white = !white;
+                    a0 = Some(a1);
+                }
+                Mode::Extension => {
+                    return Err(CcittError::Corrupt("uncompressed mode isn't supported"));
+                }
+            }
+        }
+        Ok(normalize(changes, self.columns))
+    }
+}
+
+/// Removes changes that cancel out (from runs of length 0) and ones at the end of the row,
+/// leaving only real changing elements.
+fn normalize(changes: Vec<usize>, columns: usize) -> Vec<usize> {
+    let mut normalized: Vec<usize> = Vec::with_capacity(changes.len());
+    for change in changes {
+        if normalized.last() == Some(&change) {
+            normalized.pop();
+        } else {
+            normalized.push(change);
+        }
+    }
+    normalized.retain(|&change| change < columns);
+    normalized
+}
+
+/// A table indexed by the next `MAX_CODE_LEN` bits, giving the length and value of the code
+/// they start with.
+fn lookup_table<T: Copy>(codes: &[(T, &str)]) -> Vec<Option<(u32, T)>> {
+    let mut table = vec![None; 1 << MAX_CODE_LEN];
+    for &(value, bits) in codes {
+        let len = bits.len() as u32;
+        let code = u32::from_str_radix(bits, 2).expect("code tables only contain binary digits");
+        let first = (code << (MAX_CODE_LEN - len)) as usize;
+        table[first..first + (1 << (MAX_CODE_LEN - len))].fill(Some((len, value)));
+    }
+    table
+}
+
+const MODE_CODES: [(Mode, &str); 10] = [
+    (Mode::Pass, "0001"),
+    (Mode::Horizontal, "001"),
+    (Mode::Vertical(0), "1"),
+    (Mode::Vertical(1), "011"),
+    (Mode::Vertical(2), "000011"),
+    (Mode::Vertical(3), "0000011"),
+    (Mode::Vertical(-1), "010"),
+    (Mode::Vertical(-2), "000010"),
+    (Mode::Vertical(-3), "0000010"),
+    (Mode::Extension, "0000001"),
+];
+
+/// Terminating codes for white runs of 0 to 63 pixels, then the make-up codes, including the
+/// extended ones shared with black runs.
+const WHITE_CODES: [(usize, &str); 104] = [
+    (0, "00110101"),
+    (1, "000111"),
+    (2, "0111"),
+    (3, "1000"),
+    (4, "1011"),
+    (5, "1100"),
+    (6, "1110"),
+    (7, "1111"),
+    (8, "10011"),
+    (9, "10100"),
+    (10, "00111"),
+    (11, "01000"),
+    (12, "001000"),
+    (13, "000011"),
+    (14, "110100"),
+    (15, "110101"),
+    (16, "101010"),
+    (17, "101011"),
+    (18, "0100111"),
+    (19, "0001100"),
+    (20, "0001000"),
+    (21, "0010111"),
+    (22, "0000011"),
+    (23, "0000100"),
+    (24, "0101000"),
+    (25, "0101011"),
+    (26, "0010011"),
+    (27, "0100100"),
+    (28, "0011000"),
+    (29, "00000010"),
+    (30, "00000011"),
+    (31, "00011010"),
+    (32, "00011011"),
+    (33, "00010010"),
+    (34, "00010011"),
+    (35, "00010100"),
+    (36, "00010101"),
+    (37, "00010110"),
+    (38, "00010111"),
+    (39, "00101000"),
+    (40, "00101001"),
+    (41, "00101010"),
+    (42, "00101011"),
+    (43, "00101100"),
+    (44, "00101101"),
+    (45, "00000100"),
+    (46, "00000101"),
+    (47, "00001010"),
+    (48, "00001011"),
+    (49, "01010010"),
+    (50, "01010011"),
+    (51, "01010100"),
+    (52, "01010101"),
+    (53, "00100100"),
+    (54, "00100101"),
+    (55, "01011000"),
+    (56, "01011001"),
+    (57, "01011010"),
+    (58, "01011011"),
+    (59, "01001010"),
+    (60, "01001011"),
+    (61, "00110010"),
+    (62, "00110011"),
+    (63, "00110100"),
+    (64, "11011"),
+    (128, "10010"),
+    (192, "010111"),
+    (256, "0110111"),
+    (320, "00110110"),
+    (384, "00110111"),
+    (448, "01100100"),
+    (512, "01100101"),
+    (576, "01101000"),
+    (640, "01100111"),
+    (704, "011001100"),
+    (768, "011001101"),
+    (832, "011010010"),
+    (896, "011010011"),
+    (960, "011010100"),
+    (1024, "011010101"),
+    (1088, "011010110"),
+    (1152, "011010111"),
+    (1216, "011011000"),
+    (1280, "011011001"),
+    (1344, "011011010"),
+    (1408, "011011011"),
+    (1472, "010011000"),
+    (1536, "010011001"),
+    (1600, "010011010"),
+    (1664, "011000"),
+    (1728, "010011011"),
+    (1792, "00000001000"),
+    (1856, "00000001100"),
+    (1920, "00000001101"),
+    (1984, "000000010010"),
+    (2048, "000000010011"),
+    (2112, "000000010100"),
+    (2176, "000000010101"),
+    (2240, "000000010110"),
+    (2304, "000000010111"),
+    (2368, "000000011100"),
+    (2432, "000000011101"),
+    (2496, "000000011110"),
+    (2560, "000000011111"),
+];
+
+/// Same as `WHITE_CODES`, for black runs.
+const BLACK_CODES: [(usize, &str); 104] = [
+    (0, "0000110111"),
+    (1, "010"),
+    (2, "11"),
+    (3, "10"),
+    (4, "011"),
+    (5, "0011"),
+    (6, "0010"),
+    (7, "00011"),
+    (8, "000101"),
+    (9, "000100"),
+    (10, "0000100"),
+    (11, "0000101"),
+    (12, "0000111"),
+    (13, "00000100"),
+    (14, "00000111"),
+    (15, "000011000"),
+    (16, "0000010111"),
+    (17, "0000011000"),
+    (18, "0000001000"),
+    (19, "00001100111"),
+    (20, "00001101000"),
+    (21, "00001101100"),
+    (22, "00000110111"),
+    (23, "00000101000"),
+    (24, "00000010111"),
+    (25, "00000011000"),
+    (26, "000011001010"),
+    (27, "000011001011"),
+    (28, "000011001100"),
+    (29, "000011001101"),
+    (30, "000001101000"),
+    (31, "000001101001"),
+    (32, "000001101010"),
+    (33, "000001101011"),
+    (34, "000011010010"),
+    (35, "000011010011"),
+    (36, "000011010100"),
+    (37, "000011010101"),
+    (38, "000011010110"),
+    (39, "000011010111"),
+    (40, "000001101100"),
+    (41, "000001101101"),
+    (42, "000011011010"),
+    (43, "000011011011"),
+    (44, "000001010100"),
+    (45, "000001010101"),
+    (46, "000001010110"),
+    (47, "000001010111"),
+    (48, "000001100100"),
+    (49, "000001100101"),
+    (50, "000001010010"),
+    (51, "000001010011"),
+    (52, "000000100100"),
+    (53, "000000110111"),
+    (54, "000000111000"),
+    (55, "000000100111"),
+    (56, "000000101000"),
+    (57, "000001011000"),
+    (58, "000001011001"),
+    (59, "000000101011"),
+    (60, "000000101100"),
+    (61, "000001011010"),
+    (62, "000001100110"),
+    (63, "000001100111"),
+    (64, "0000001111"),
+    (128, "000011001000"),
+    (192, "000011001001"),
+    (256, "000001011011"),
+    (320, "000000110011"),
+    (384, "000000110100"),
+    (448, "000000110101"),
+    (512, "0000001101100"),
+    (576, "0000001101101"),
+    (640, "0000001001010"),
+    (704, "0000001001011"),
+    (768, "0000001001100"),
+    (832, "0000001001101"),
+    (896, "0000001110010"),
+    (960, "0000001110011"),
+    (1024, "0000001110100"),
+    (1088, "0000001110101"),
+    (1152, "0000001110110"),
+    (1216, "0000001110111"),
+    (1280, "0000001010010"),
+    (1344, "0000001010011"),
+    (1408, "0000001010100"),
+    (1472, "0000001010101"),
+    (1536, "0000001011010"),
+    (1600, "0000001011011"),
+    (1664, "0000001100100"),
+    (1728, "0000001100101"),
+    (1792, "00000001000"),
+    (1856, "00000001100"),
+    (1920, "00000001101"),
+    (1984, "000000010010"),
+    (2048, "000000010011"),
+    (2112, "000000010100"),
+    (2176, "000000010101"),
+    (2240, "000000010110"),
+    (2304, "000000010111"),
+    (2368, "000000011100"),
+    (2432, "000000011101"),
+    (2496, "000000011110"),
+    (2560, "000000011111"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+    use crate::filter::parms;
+    use crate::{Name, Object};
+
+    // Most vectors are this 8x2 image, `..###...` over `...####.` with `#` black
+    const IMAGE: [u8; 2] = [0xC7, 0xE1];
+
+    fn run(
+        data: &str,
+        ints: &[(&'static str, i64)],
+        bools: &[(&'static str, bool)],
+    ) -> Result<Vec<u8>, FilterError> {
+        let mut parms = parms(ints);
+        for &(key, value) in bools {
+            parms.insert(Name::from(key), Object::Bool(value));
+        }
+        decode(&hex(data), Some(&parms), 1 << 20)
+    }
+
+    #[test]
+    fn group_3_1d() {
+        let data = "7a218e";
+        assert_eq!(
+            run(data, &[("Columns", 8), ("Rows", 2)], &[]).unwrap(),
+            IMAGE
+        );
+
+        // With EOLs, and the first row again after the RTC that ends the data
+        let data = "0017a000618e002002002002002002f400";
+        assert_eq!(run(data, &[("Columns", 8)], &[]).unwrap(), IMAGE);
+        assert_eq!(
+            run(data, &[("Columns", 8)], &[("EndOfBlock", false)]).unwrap(),
+            [IMAGE[0], IMAGE[1], IMAGE[0]]
+        );
+    }
+
+    #[test]
+    fn group_3_2d() {
+        // A 1D row and then a 2D one, each after an EOL and its tag bit
+        let data = "001bd000261c006003001800c0060030";
+        assert_eq!(run(data, &[("K", 2), ("Columns", 8)], &[]).unwrap(), IMAGE);
+    }
+
+    #[test]
+    fn group_4() {
+        let data = "2f5870";
+        assert_eq!(run(data, &[("K", -1), ("Columns", 8)], &[]).unwrap(), IMAGE);
+        assert_eq!(
+            run(data, &[("K", -1), ("Columns", 8), ("Rows", 1)], &[]).unwrap(),
+            IMAGE[..1]
+        );
+    }
+
+    #[test]
+    fn black_is_1() {
+        assert_eq!(
+            run(
+                "2f5870",
+                &[("K", -1), ("Columns", 8)],
+                &[("BlackIs1", true)]
+            )
+            .unwrap(),
+            [0x38, 0x1E]
+        );
+    }
+
+    #[test]
+    fn encoded_byte_align() {
+        let aligned = [("EncodedByteAlign", true)];
+        // Rows starting on byte boundaries
+        assert_eq!(run("7a008638", &[("Columns", 8)], &aligned).unwrap(), IMAGE);
+        // EOLs ending on them
+        assert_eq!(
+            run("00017a00018638", &[("Columns", 8)], &aligned).unwrap(),
+            IMAGE
+        );
+    }
+
+    #[test]
+    fn limit() {
+        let g4 = parms(&[("K", -1), ("Columns", 8)]);
+        assert!(matches!(
+            decode(&hex("2f5870"), Some(&g4), 1),
+            Err(FilterError::TooLarge { .. })
+        ));
+
+        // A row of white pixels takes a bit without `/Rows`, and none is decoded with it
+        let wide = parms(&[("K", -1), ("Columns", 1 << 24)]);
+        assert!(matches!(
+            decode(&[0xFF; 1024], Some(&wide), 1 << 24),
+            Err(FilterError::TooLarge { .. })
+        ));
+        let tall = parms(&[("K", -1), ("Columns", 1 << 24), ("Rows", 1 << 20)]);
+        assert!(matches!(
+            decode(&[], Some(&tall), 1 << 24),
+            Err(FilterError::TooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+//! `/DCTDecode`: baseline and progressive JPEG. The data is passed through as it is, but the
+//! frame header is read to find out what the image is.
+
+use super::{corrupt, FilterError, ImageInfo};
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(pos..pos + 2)?.try_into().unwrap(),
+    ))
+}
+
+/// Reads the image size and format from the start of frame (SOFn) marker segment.
+pub fn probe(data: &[u8]) -> Result<ImageInfo, FilterError> {
+    let ends_early = || corrupt("DCTDecode", "data ends before the frame header");
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(corrupt(
+            "DCTDecode",
+            "data doesn't start with an SOI marker",
+        ));
+    }
+
+    let mut pos = 2;
+    loop {
+        // Markers can be padded with any number of 0xFF bytes
+        if data.get(pos) != Some(&0xFF) {
+            return Err(data.get(pos).map_or_else(ends_early, |byte| {
+                corrupt(
+                    "DCTDecode",
+                    format!("expected a marker at byte {}, found {:#04X}", pos, byte),
+                )
+            }));
+        }
+        while data.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = *data.get(pos).ok_or_else(ends_early)?;
+        pos += 1;
+
+        match marker {
+            // Markers without a segment
+            0x01 | 0xD0..=0xD7 => continue,
+            // SOF0 to SOF15, except DHT, JPG and DAC, which share the range
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let segment = data.get(pos + 2..pos + 8).ok_or_else(ends_early)?;
+                let mut height = u16::from_be_bytes([segment[1], segment[2]]) as u32;
+                if height == 0 {
+                    // The height is given by a DNL marker after the first scan instead
+                    height = data
+                        .windows(4)
+                        .skip(pos)
+                        .position(|window| window == [0xFF, 0xDC, 0x00, 0x04])
+                        .and_then(|offset| read_u16(data, pos + offset + 4))
+                        .ok_or_else(|| corrupt("DCTDecode", "no height, and no DNL marker"))?
+                        as u32;
+                }
+                return Ok(ImageInfo {
+                    width: u16::from_be_bytes([segment[3], segment[4]]) as u32,
+                    height,
+                    components: segment[5] as u16,
+                    bits_per_component: segment[0],
+                });
+            }
+            0xD9 | 0xDA => {
+                return Err(corrupt(
+                    "DCTDecode",
+                    "no frame header before the first scan",
+                ));
+            }
+            _ => {
+                let len = read_u16(data, pos).ok_or_else(ends_early)?;
+                pos += len as usize;
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! `/FlateDecode`: zlib (RFC 1950) wrapped deflate (RFC 1951) data.
+//!
+//! The Huffman decoding works one bit at a time, along the lines of zlib's `puff.c`. It's not
+//! the fastest way to do it, but it's short and easy to check against the RFC.
+
+use super::{too_large, FilterError};
+
+const MAX_BITS: usize = 15;
+
+/// Base lengths for length codes 257..=285, and how many extra bits follow each.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances for distance codes 0..=29, and how many extra bits follow each.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order code length code lengths are stored in, in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+enum InflateError {
+    /// Ran out of input in the middle of a block.
+    Eof,
+    Corrupt(&'static str),
+    /// The output would be longer than the limit.
+    TooLarge,
+}
+
+/// Inflates zlib data. Raw deflate data without the zlib header is accepted too, since some
+/// producers write that instead.
+///
+/// Truncated data is decoded as far as it goes rather than rejected, as plenty of real files
+/// have streams that were cut short. Data that inflates to more than `limit` bytes is
+/// rejected, however.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, FilterError> {
+    let body = match data {
+        [cmf, flg, rest @ ..] if cmf & 0x0F == 8 && (*cmf as u16 * 256 + *flg as u16) % 31 == 0 => {
+            if flg & 0x20 != 0 {
+                return Err(corrupt("preset dictionaries aren't allowed in PDF"));
+            }
+            rest
+        }
+        _ => data,
+    };
+
+    let mut out = Vec::with_capacity(data.len().saturating_mul(4).min(limit));
+    match Inflater::new(body, limit).run(&mut out) {
+        Ok(()) | Err(InflateError::Eof) => Ok(out),
+        Err(InflateError::Corrupt(message)) => Err(corrupt(message)),
+        Err(InflateError::TooLarge) => Err(too_large("FlateDecode", limit)),
+    }
+}
+
+fn corrupt(message: &str) -> FilterError {
+    super::corrupt("FlateDecode", message)
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols
+/// ordered by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the code from the code length of each symbol (0 meaning unused). Incomplete codes
+    /// are allowed, as deflate uses them for single distance codes.
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(InflateError::Corrupt("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Self { counts, symbols })
+    }
+}
+
+struct Inflater<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+    /// The most bytes the output may grow to.
+    limit: usize,
+}
+
+impl<'a> Inflater<'a> {
+    fn new(data: &'a [u8], limit: usize) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bit_buf: 0,
+            bit_count: 0,
+            limit,
+        }
+    }
+
+    fn run(&mut self, out: &mut Vec<u8>) -> Result<(), InflateError> {
+        loop {
+            let last = self.bits(1)? == 1;
+            match self.bits(2)? {
+                0 => self.stored(out)?,
+                1 => self.fixed(out)?,
+                2 => self.dynamic(out)?,
+                _ => return Err(InflateError::Corrupt("invalid block type")),
+            }
+            if last {
+                return Ok(());
+            }
+        }
+    }
+
+    fn bits(&mut self, need: u32) -> Result<u32, InflateError> {
+        while self.bit_count < need {
+            let &byte = self.data.get(self.pos).ok_or(InflateError::Eof)?;
+            self.pos += 1;
+            self.bit_buf |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buf & ((1u32 << need) - 1);
+        self.bit_buf >>= need;
+        self.bit_count -= need;
+        Ok(value)
+    }
+
+    /// Fails if `out` can't take another `len` bytes.
+    fn reserve(&self, out: &[u8], len: usize) -> Result<(), InflateError> {
+        if len > self.limit - out.len() {
+            return Err(InflateError::TooLarge);
+        }
+        Ok(())
+    }
+
+    fn stored(&mut self, out: &mut Vec<u8>) -> Result<(), InflateError> {
+        // Stored blocks start on a byte boundary
+        self.bit_buf = 0;
+        self.bit_count = 0;
+
+        let header = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .ok_or(InflateError::Eof)?;
+        let len = u16::from_le_bytes([header[0], header[1]]);
+        let nlen = u16::from_le_bytes([header[2], header[3]]);
+        if len != !nlen {
+            return Err(InflateError::Corrupt(
+                "stored block length doesn't match its complement",
+            ));
+        }
+        self.pos += 4;
+
+        let end = self.pos + len as usize;
+        let available = &self.data[self.pos..end.min(self.data.len())];
+        self.reserve(out, available.len())?;
+        out.extend_from_slice(available);
+        self.pos += available.len();
+        if available.len() < len as usize {
+            return Err(InflateError::Eof);
+        }
+        Ok(())
+    }
+
+    fn fixed(&mut self, out: &mut Vec<u8>) -> Result<(), InflateError> {
+        let mut lengths = [0u8; 288];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        let lit_len = Huffman::new(&lengths)?;
+        let dist = Huffman::new(&[5; 30])?;
+        self.codes(out, &lit_len, &dist)
+    }
+
+    fn dynamic(&mut self, out: &mut Vec<u8>) -> Result<(), InflateError> {
+        let n_lit_len = self.bits(5)? as usize + 257;
+        let n_dist = self.bits(5)? as usize + 1;
+        let n_code_len = self.bits(4)? as usize + 4;
+        if n_lit_len > 286 || n_dist > 30 {
+            return Err(InflateError::Corrupt("too many length or distance codes"));
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for &index in &CODE_LENGTH_ORDER[..n_code_len] {
+            code_lengths[index] = self.bits(3)? as u8;
+        }
+        let code_len = Huffman::new(&code_lengths)?;
+
+        let mut lengths = vec![0u8; n_lit_len + n_dist];
+        let mut i = 0;
+        while i < lengths.len() {
+            let symbol = self.decode(&code_len)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    if i == 0 {
+                        return Err(InflateError::Corrupt(
+                            "repeated code length with no previous length",
+                        ));
+                    }
+                    (lengths[i - 1], 3 + self.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.bits(3)? as usize),
+                _ => (0, 11 + self.bits(7)? as usize),
+            };
+            if i + repeat > lengths.len() {
+                return Err(InflateError::Corrupt(
+                    "code lengths overflow the block header",
+                ));
+            }
+            lengths[i..i + repeat].fill(value);
+            i += repeat;
+        }
+
+        if lengths[256] == 0 {
+            return Err(InflateError::Corrupt("no end-of-block code"));
+        }
+        let lit_len = Huffman::new(&lengths[..n_lit_len])?;
+        let dist = Huffman::new(&lengths[n_lit_len..])?;
+        self.codes(out, &lit_len, &dist)
+    }
+
+    /// Decodes literals and length/distance pairs until the end of the block.
+    fn codes(
+        &mut self,
+        out: &mut Vec<u8>,
+        lit_len: &Huffman,
+        dist: &Huffman,
+    ) -> Result<(), InflateError> {
+        loop {
+            let symbol = self.decode(lit_len)? as usize;
+            match symbol {
+                0..=255 => {
+                    self.reserve(out, 1)?;
+                    out.push(symbol as u8);
+                }
+                256 => return Ok(()),
+                257..=285 => {
+                    let index = symbol - 257;
+                    let len = LENGTH_BASE[index] as usize
+                        + self.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+                    let index = self.decode(dist)? as usize;
+                    if index >= DIST_BASE.len() {
+                        return Err(InflateError::Corrupt("invalid distance code"));
+                    }
+                    let distance =
+                        DIST_BASE[index] as usize + self.bits(DIST_EXTRA[index] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err(InflateError::Corrupt("distance too far back"));
+                    }
+
+                    // The copy may overlap what it's producing, so go byte by byte
+                    self.reserve(out, len)?;
+                    let start = out.len() - distance;
+                    for i in 0..len {
+                        out.push(out[start + i]);
+                    }
+                }
+                _ => return Err(InflateError::Corrupt("invalid literal/length code")),
+            }
+        }
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16, InflateError> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for &count in &huffman.counts[1..] {
+            code |= self.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateError::Corrupt("invalid Huffman code"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+
+    const LIMIT: usize = 1 << 20;
+
+    #[test]
+    fn stored_block() {
+        let data = hex("7801010c00f3ff53746f72656420626c6f636b1e00049d");
+        assert_eq!(inflate(&data, LIMIT).unwrap(), b"Stored block");
+    }
+
+    #[test]
+    fn fixed_block() {
+        let data = hex("78da73cbac484d51f0284d4bcb4dcc5348ce4f492dd65148c3140400254c0e73");
+        assert_eq!(
+            inflate(&data, LIMIT).unwrap(),
+            b"Fixed Huffman codes, fixed Huffman codes"
+        );
+    }
+
+    #[test]
+    fn dynamic_block() {
+        let data = hex(concat!(
+            "78da85cbc90980301404d056a60091ec4b3906be7808063460fb16900f737ebc5ad1c69c5d5e8c134d",
+            "e4c1b8312fc177f4bea12ebea316728a7232395939899ca49c484e544e202728c793e395e3c871cab1",
+            "e458e51872cc7a7efc8fac9f",
+        ));
+        let text: String = (90..=99)
+            .rev()
+            .map(|n| format!("{} bottles of beer on the wall, {} bottles of beer. ", n, n))
+            .collect();
+        assert_eq!(inflate(&data, LIMIT).unwrap(), text.as_bytes());
+    }
+
+    #[test]
+    fn zlib_header() {
+        // Raw deflate data is taken as it is
+        let data = hex("2b4a2c5748494dcb492c49d551c8cb57a8cac94c52c8484d4c492d0200");
+        assert_eq!(
+            inflate(&data, LIMIT).unwrap(),
+            b"raw deflate, no zlib header"
+        );
+
+        // A preset dictionary isn't
+        let data = hex("78f916c004374b81b314caf38b528a01376a0686");
+        assert!(matches!(
+            inflate(&data, LIMIT),
+            Err(FilterError::Corrupt { .. })
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let data = hex("78da73cbac484d51f0284d4bcb4dcc5348ce4f492dd65148c3140400254c0e73");
+        assert_eq!(inflate(&data[..16], LIMIT).unwrap(), b"Fixed Huffman");
+    }
+
+    #[test]
+    fn limit() {
+        // 1000 zero bytes
+        let data = hex("78da63601805a360140c77000003e80001");
+        assert_eq!(inflate(&data, 1000).unwrap(), vec![0; 1000]);
+        assert_eq!(
+            inflate(&data, 999),
+            Err(FilterError::TooLarge {
+                filter: "FlateDecode",
+                limit: 999
+            })
+        );
+    }
+}
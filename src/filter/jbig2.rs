@@ -0,0 +1,740 @@
+//! `/JBIG2Decode`: bilevel images in the ITU-T T.88 (JBIG2) format, embedded as in its annex D.3:
+//! a sequence of segments without the file header, with the segments shared between images in
+//! a separate `/JBIG2Globals` stream.
+//!
+//! Generic, refinement and text regions and symbol dictionaries are supported, all with
+//! arithmetic coding (plus MMR for generic regions); Huffman coding and halftones aren't.
+
+mod arith;
+mod region;
+mod symbol;
+
+use std::collections::HashMap;
+
+use self::arith::MqDecoder;
+use self::region::{GenericParams, RefinementParams};
+use self::symbol::{SymbolDictParams, TextDecoders, TextParams};
+use super::{ccitt, too_large, FilterError};
+use crate::{Dict, Object};
+
+fn corrupt(message: impl Into<String>) -> FilterError {
+    super::corrupt("JBIG2Decode", message)
+}
+
+fn unsupported(feature: &str) -> FilterError {
+    FilterError::Unsupported(format!("JBIG2 {}", feature))
+}
+
+/// Decodes the first page in `data` (after the segments in the `/JBIG2Globals` stream, if
+/// any) into rows of packed pixels. Black pixels are 0 bits, as for an image without a
+/// `/Decode` array, even though JBIG2 itself has them the other way round.
+///
+/// Bitmaps are kept a byte per pixel while decoding, so `limit` caps the pixels in any one
+/// of them rather than the decoded data.
+pub fn decode(data: &[u8], parms: Option<&Dict>, limit: usize) -> Result<Vec<u8>, FilterError> {
+    let mut decoder = Decoder {
+        results: HashMap::new(),
+        page: None,
+        limit,
+    };
+    match parms.and_then(|parms| parms.get("JBIG2Globals")) {
+        Some(Object::Stream {
+            decoded: Some(globals),
+            ..
+        }) => decoder.run(globals)?,
+        Some(Object::Stream { decoded: None, .. }) => {
+            return Err(corrupt("the `JBIG2Globals` stream couldn't be decoded"));
+        }
+        Some(Object::Null) | None => {}
+        Some(_) => return Err(corrupt("`JBIG2Globals` must be a stream")),
+    }
+    decoder.run(data)?;
+
+    let page = decoder
+        .page
+        .ok_or_else(|| corrupt("no page information segment"))?;
+    Ok(page.bitmap.pack())
+}
+
+/// A bilevel image, one byte per pixel, 1 being black.
+#[derive(Clone)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    /// A bitmap of `value` pixels, unless it would have more than `limit` pixels.
+    pub fn new(width: usize, height: usize, value: u8, limit: usize) -> Result<Self, FilterError> {
+        match width.checked_mul(height) {
+            Some(size) if size <= limit => Ok(Self {
+                width,
+                height,
+                pixels: vec![value; size],
+            }),
+            _ => Err(too_large("JBIG2Decode", limit)),
+        }
+    }
+
+    /// The pixel at `x`, `y`; pixels outside the bitmap are white.
+    pub fn get(&self, x: isize, y: isize) -> u8 {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return 0;
+        }
+        self.pixels[y as usize * self.width + x as usize]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        self.pixels[y * self.width + x] = value;
+    }
+
+    pub fn copy_row(&mut self, from: usize, to: usize) {
+        let width = self.width;
+        self.pixels
+            .copy_within(from * width..(from + 1) * width, to * width);
+    }
+
+    /// Draws `other` onto this bitmap with its top left corner at `x`, `y`.
+    pub fn combine(&mut self, other: &Bitmap, x: isize, y: isize, op: Combination) {
+        for oy in 0..other.height {
+            let ty = y + oy as isize;
+            if ty < 0 || ty as usize >= self.height {
+                continue;
+            }
+            for ox in 0..other.width {
+                let tx = x + ox as isize;
+                if tx < 0 || tx as usize >= self.width {
+                    continue;
+                }
+                let target = &mut self.pixels[ty as usize * self.width + tx as usize];
+                let source = other.pixels[oy * other.width + ox];
+                *target = match op {
+                    Combination::Or => *target | source,
+                    Combination::And => *target & source,
+                    Combination::Xor => *target ^ source,
+                    Combination::Xnor => 1 - (*target ^ source),
+                    Combination::Replace => source,
+                };
+            }
+        }
+    }
+
+    /// The part of this bitmap at `x`, `y` of the given size.
+    fn crop(
+        &self,
+        x: isize,
+        y: isize,
+        width: usize,
+        height: usize,
+        limit: usize,
+    ) -> Result<Bitmap, FilterError> {
+        let mut cropped = Bitmap::new(width, height, 0, limit)?;
+        for cy in 0..height {
+            for cx in 0..width {
+                cropped.set(cx, cy, self.get(x + cx as isize, y + cy as isize));
+            }
+        }
+        Ok(cropped)
+    }
+
+    /// Adds rows of `value` pixels at the bottom, up to `height` rows in total.
+    fn grow(&mut self, height: usize, value: u8, limit: usize) -> Result<(), FilterError> {
+        if height > self.height {
+            if self.width.saturating_mul(height) > limit {
+                return Err(too_large("JBIG2Decode", limit));
+            }
+            self.pixels.resize(self.width * height, value);
+            self.height = height;
+        }
+        Ok(())
+    }
+
+    /// Packs the pixels into rows of bits padded to whole bytes, with 0 bits for black.
+    fn pack(&self) -> Vec<u8> {
+        let row_len = self.width.div_ceil(8);
+        let mut packed = vec![0xFF; row_len * self.height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.pixels[y * self.width + x] == 1 {
+                    packed[y * row_len + x / 8] ^= 0x80 >> (x % 8);
+                }
+            }
+        }
+        packed
+    }
+}
+
+/// How a region is drawn onto the page (or a symbol onto a text region).
+#[derive(Clone, Copy)]
+pub enum Combination {
+    Or,
+    And,
+    Xor,
+    Xnor,
+    Replace,
+}
+
+impl Combination {
+    fn from_bits(bits: u8) -> Result<Self, FilterError> {
+        match bits {
+            0 => Ok(Combination::Or),
+            1 => Ok(Combination::And),
+            2 => Ok(Combination::Xor),
+            3 => Ok(Combination::Xnor),
+            4 => Ok(Combination::Replace),
+            _ => Err(corrupt(format!("invalid combination operator {}", bits))),
+        }
+    }
+}
+
+/// Big endian reader for segment headers and data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], FilterError> {
+        let bytes = self
+            .pos
+            .checked_add(n)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| corrupt("unexpected end of data"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, FilterError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FilterError> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, FilterError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// `n` adaptive template pixel positions, each a signed x and y byte.
+    fn at_pixels<const N: usize>(&mut self, n: usize) -> Result<[(i8, i8); N], FilterError> {
+        let mut at = [(0, 0); N];
+        for pixel in &mut at[..n] {
+            *pixel = (self.u8()? as i8, self.u8()? as i8);
+        }
+        Ok(at)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos..];
+        self.pos = self.data.len();
+        rest
+    }
+}
+
+struct Segment<'a> {
+    number: u32,
+    kind: u8,
+    referred: Vec<u32>,
+    data: &'a [u8],
+}
+
+/// The region segment information field at the start of every region segment.
+struct RegionInfo {
+    width: usize,
+    height: usize,
+    x: isize,
+    y: isize,
+    combination: Combination,
+}
+
+impl RegionInfo {
+    fn read(reader: &mut Reader) -> Result<Self, FilterError> {
+        Ok(Self {
+            width: reader.u32()? as usize,
+            height: reader.u32()? as usize,
+            x: reader.u32()? as i32 as isize,
+            y: reader.u32()? as i32 as isize,
+            combination: Combination::from_bits(reader.u8()? & 7)?,
+        })
+    }
+}
+
+/// What decoding a segment left for later segments to refer to.
+enum Stored {
+    Symbols(Vec<Bitmap>),
+    /// An intermediate region, to be refined by a later segment.
+    Region(Bitmap),
+}
+
+struct Page {
+    bitmap: Bitmap,
+    default_pixel: u8,
+    /// Whether the page's height is only known once its last stripe ends.
+    unknown_height: bool,
+}
+
+struct Decoder {
+    results: HashMap<u32, Stored>,
+    page: Option<Page>,
+    /// The most pixels in any one bitmap.
+    limit: usize,
+}
+
+impl Decoder {
+    /// Decodes all the segments in `data`, up to the end of the first page.
+    fn run(&mut self, data: &[u8]) -> Result<(), FilterError> {
+        let mut reader = Reader { data, pos: 0 };
+        while reader.pos < data.len() {
+            let segment = Self::read_segment(&mut reader)?;
+            match segment.kind {
+                0 => self.symbol_dictionary(&segment)?,
+                4 | 6 | 7 => self.text_region(&segment)?,
+                16 | 20 | 22 | 23 => return Err(unsupported("halftone regions")),
+                36 | 38 | 39 => self.generic_region(&segment)?,
+                40 | 42 | 43 => self.refinement_region(&segment)?,
+                48 => self.page_information(&segment)?,
+                // End of page, end of file
+                49 | 51 => break,
+                50 => {
+                    let end_row = Reader {
+                        data: segment.data,
+                        pos: 0,
+                    }
+                    .u32()? as usize;
+                    let limit = self.limit;
+                    let page = self.page()?;
+                    if page.unknown_height {
+                        page.bitmap.grow(end_row + 1, page.default_pixel, limit)?;
+                    }
+                }
+                // Profiles, code tables (only used by Huffman coding) and extensions
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn read_segment<'a>(reader: &mut Reader<'a>) -> Result<Segment<'a>, FilterError> {
+        let number = reader.u32()?;
+        let flags = reader.u8()?;
+        let kind = flags & 0x3F;
+
+        let count = match reader.data.get(reader.pos) {
+            Some(byte) if byte >> 5 == 7 => {
+                // The long form, followed by a bit per segment (and this one) saying whether
+                // it's retained
+                let count = (reader.u32()? & 0x1FFF_FFFF) as usize;
+                reader.bytes((count + 1).div_ceil(8))?;
+                count
+            }
+            _ => (reader.u8()? >> 5) as usize,
+        };
+        let referred = (0..count)
+            .map(|_| match number {
+                0..=256 => Ok(reader.u8()? as u32),
+                257..=65536 => Ok(reader.u16()? as u32),
+                _ => reader.u32(),
+            })
+            .collect::<Result<_, _>>()?;
+        // The page this segment belongs to, which doesn't matter for a single page
+        reader.bytes(if flags & 0x40 != 0 { 4 } else { 1 })?;
+
+        let len = match reader.u32()? {
+            // Only immediate generic regions may leave out their length, and then end with a
+            // marker followed by the number of rows
+            0xFFFF_FFFF if kind == 38 => {
+                let rest = &reader.data[reader.pos..];
+                let mmr = rest.get(17).is_some_and(|flags| flags & 1 != 0);
+                let marker: &[u8] = if mmr { &[0x00, 0x00] } else { &[0xFF, 0xAC] };
+                rest.windows(2)
+                    .skip(18)
+                    .position(|window| window == marker)
+                    .map(|pos| 18 + pos + 2 + 4)
+                    .ok_or_else(|| corrupt("generic region of unknown length has no end marker"))?
+            }
+            len => len as usize,
+        };
+
+        Ok(Segment {
+            number,
+            kind,
+            referred,
+            data: reader.bytes(len)?,
+        })
+    }
+
+    fn page(&mut self) -> Result<&mut Page, FilterError> {
+        self.page
+            .as_mut()
+            .ok_or_else(|| corrupt("region segment before the page information segment"))
+    }
+
+    /// The symbols exported by the symbol dictionaries `segment` refers to.
+    fn referred_symbols(&self, segment: &Segment) -> Vec<&Bitmap> {
+        segment
+            .referred
+            .iter()
+            .filter_map(|number| match self.results.get(number) {
+                Some(Stored::Symbols(symbols)) => Some(symbols),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Stores an intermediate region, or draws an immediate one onto the page.
+    fn region_result(
+        &mut self,
+        segment: &Segment,
+        info: RegionInfo,
+        bitmap: Bitmap,
+    ) -> Result<(), FilterError> {
+        if matches!(segment.kind, 4 | 20 | 36 | 40) {
+            self.results.insert(segment.number, Stored::Region(bitmap));
+            return Ok(());
+        }
+
+        let limit = self.limit;
+        let page = self.page()?;
+        if page.unknown_height {
+            let bottom = usize::try_from(info.y).unwrap_or(0) + info.height;
+            page.bitmap.grow(bottom, page.default_pixel, limit)?;
+        }
+        page.bitmap
+            .combine(&bitmap, info.x, info.y, info.combination);
+        Ok(())
+    }
+
+    fn page_information(&mut self, segment: &Segment) -> Result<(), FilterError> {
+        let mut reader = Reader {
+            data: segment.data,
+            pos: 0,
+        };
+        let width = reader.u32()? as usize;
+        let height = reader.u32()?;
+        // Resolution
+        reader.bytes(8)?;
+        let flags = reader.u8()?;
+        let default_pixel = (flags >> 2) & 1;
+
+        let unknown_height = height == 0xFFFF_FFFF;
+        let height = if unknown_height { 0 } else { height as usize };
+        self.page = Some(Page {
+            bitmap: Bitmap::new(width, height, default_pixel, self.limit)?,
+            default_pixel,
+            unknown_height,
+        });
+        Ok(())
+    }
+
+    fn symbol_dictionary(&mut self, segment: &Segment) -> Result<(), FilterError> {
+        let mut reader = Reader {
+            data: segment.data,
+            pos: 0,
+        };
+        let flags = reader.u16()?;
+        if flags & 1 != 0 {
+            return Err(unsupported("Huffman coding"));
+        }
+        if flags & 0x100 != 0 {
+            return Err(unsupported("reused arithmetic coding contexts"));
+        }
+        let refine_aggregate = flags & 2 != 0;
+        let template = ((flags >> 10) & 3) as usize;
+        let refine_template = ((flags >> 12) & 1) as usize;
+
+        let at = reader.at_pixels(if template == 0 { 4 } else { 1 })?;
+        let refine_at = if refine_aggregate && refine_template == 0 {
+            reader.at_pixels(2)?
+        } else {
+            [(0, 0); 2]
+        };
+        // The number of exported symbols is implied by the export flags
+        reader.u32()?;
+        let num_new = reader.u32()? as usize;
+
+        let input = self.referred_symbols(segment);
+        let symbols = symbol::decode_symbol_dictionary(
+            &mut MqDecoder::new(reader.rest()),
+            &input,
+            &SymbolDictParams {
+                refine_aggregate,
+                generic: GenericParams {
+                    template,
+                    tpgdon: false,
+                    at,
+                    limit: self.limit,
+                },
+                refine: RefinementParams {
+                    template: refine_template,
+                    tpgron: false,
+                    at: refine_at,
+                    limit: self.limit,
+                },
+                num_new,
+            },
+        )?;
+        self.results
+            .insert(segment.number, Stored::Symbols(symbols));
+        Ok(())
+    }
+
+    fn text_region(&mut self, segment: &Segment) -> Result<(), FilterError> {
+        let mut reader = Reader {
+            data: segment.data,
+            pos: 0,
+        };
+        let info = RegionInfo::read(&mut reader)?;
+        let flags = reader.u16()?;
+        if flags & 1 != 0 {
+            return Err(unsupported("Huffman coding"));
+        }
+        let refine = flags & 2 != 0;
+        let refine_template = ((flags >> 15) & 1) as usize;
+        let refine_at = if refine && refine_template == 0 {
+            reader.at_pixels(2)?
+        } else {
+            [(0, 0); 2]
+        };
+        let num_instances = reader.u32()? as usize;
+
+        // SBDSOFFSET is a signed 5 bit number
+        let ds_offset = ((flags >> 10) & 0x1F) as i64;
+        let params = TextParams {
+            width: info.width,
+            height: info.height,
+            num_instances,
+            strip_size: 1 << ((flags >> 2) & 3),
+            ref_corner: ((flags >> 4) & 3) as u8,
+            transposed: flags & 0x40 != 0,
+            combination: Combination::from_bits(((flags >> 7) & 3) as u8)?,
+            default_pixel: ((flags >> 9) & 1) as u8,
+            ds_offset: if ds_offset >= 16 {
+                ds_offset - 32
+            } else {
+                ds_offset
+            },
+            refine: refine.then_some(RefinementParams {
+                template: refine_template,
+                tpgron: false,
+                at: refine_at,
+                limit: self.limit,
+            }),
+            limit: self.limit,
+        };
+
+        let symbols = self.referred_symbols(segment);
+        let bitmap = symbol::decode_text_region(
+            &mut MqDecoder::new(reader.rest()),
+            &mut TextDecoders::new(symbols.len())?,
+            &symbols,
+            &params,
+        )?;
+        self.region_result(segment, info, bitmap)
+    }
+
+    fn generic_region(&mut self, segment: &Segment) -> Result<(), FilterError> {
+        let mut reader = Reader {
+            data: segment.data,
+            pos: 0,
+        };
+        let mut info = RegionInfo::read(&mut reader)?;
+        let flags = reader.u8()?;
+        let mmr = flags & 1 != 0;
+        let template = ((flags >> 1) & 3) as usize;
+        let at = if mmr {
+            [(0, 0); 4]
+        } else {
+            reader.at_pixels(if template == 0 { 4 } else { 1 })?
+        };
+
+        let mut data = reader.rest();
+        if info.height == 0xFFFF_FFFF {
+            // The real height follows the data
+            let split = data
+                .len()
+                .checked_sub(4)
+                .ok_or_else(|| corrupt("generic region of unknown height has no row count"))?;
+            let rows;
+            (data, rows) = data.split_at(split);
+            info.height = u32::from_be_bytes(rows.try_into().unwrap()) as usize;
+        }
+
+        let bitmap = if mmr {
+            let mut bitmap = Bitmap::new(info.width, info.height, 0, self.limit)?;
+            if info.width > 0 && info.height > 0 {
+                let packed = ccitt::decode_with(
+                    data,
+                    &ccitt::Options {
+                        k: -1,
+                        columns: info.width,
+                        rows: info.height,
+                        byte_align: false,
+                        end_of_block: true,
+                        black_is_1: true,
+                        limit: self.limit,
+                    },
+                )?;
+                let row_len = info.width.div_ceil(8);
+                for (y, row) in packed.chunks(row_len).take(info.height).enumerate() {
+                    for x in 0..info.width {
+                        bitmap.set(x, y, (row[x / 8] >> (7 - x % 8)) & 1);
+                    }
+                }
+            }
+            bitmap
+        } else {
+            region::decode_generic(
+                &mut MqDecoder::new(data),
+                &mut vec![0; region::GENERIC_CONTEXTS],
+                info.width,
+                info.height,
+                &GenericParams {
+                    template,
+                    tpgdon: flags & 8 != 0,
+                    at,
+                    limit: self.limit,
+                },
+            )?
+        };
+        self.region_result(segment, info, bitmap)
+    }
+
+    fn refinement_region(&mut self, segment: &Segment) -> Result<(), FilterError> {
+        let mut reader = Reader {
+            data: segment.data,
+            pos: 0,
+        };
+        let info = RegionInfo::read(&mut reader)?;
+        let flags = reader.u8()?;
+        let template = (flags & 1) as usize;
+        let at = if template == 0 {
+            reader.at_pixels(2)?
+        } else {
+            [(0, 0); 2]
+        };
+
+        // The region being refined is either an intermediate region or part of the page
+        let limit = self.limit;
+        let reference = match segment.referred.first() {
+            Some(number) => match self.results.remove(number) {
+                Some(Stored::Region(bitmap)) => bitmap,
+                _ => return Err(corrupt("refinement of a segment that isn't a region")),
+            },
+            None => self
+                .page()?
+                .bitmap
+                .crop(info.x, info.y, info.width, info.height, limit)?,
+        };
+
+        let bitmap = region::decode_refinement(
+            &mut MqDecoder::new(reader.rest()),
+            &mut vec![0; region::REFINEMENT_CONTEXTS],
+            info.width,
+            info.height,
+            &reference,
+            (0, 0),
+            &RefinementParams {
+                template,
+                tpgron: flags & 2 != 0,
+                at,
+                limit: self.limit,
+            },
+        )?;
+        self.region_result(segment, info, bitmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+    use crate::filter::parms;
+    use crate::Name;
+    use std::borrow::Cow;
+
+    // The segments were made with an independent encoder; the expected pages are packed with
+    // 0 bits for black, as `decode` returns them
+    const GENERIC: &str = "\
+        0000000030000100000013000000100000000800000000000000000000000000\
+        0001260001000000260000000c00000006000000020000000100080\
+        3fffdff02fefefe68ee081d2625f2c2473fffac\
+        0000000231000100000000";
+
+    #[test]
+    fn generic_region() {
+        assert_eq!(
+            decode(&hex(GENERIC), None, 1 << 20).unwrap(),
+            hex("ffffef17c76bdff7e507ff73cef3ffff")
+        );
+    }
+
+    #[test]
+    fn mmr_region() {
+        let data = "\
+            00000000300001000000130000000d0000000400000000000000000000000000\
+            00012600010000001f0000000d000000040000000000000000000130\
+            8c6082fffe2102a550010010\
+            0000000231000100000000";
+        assert_eq!(
+            decode(&hex(data), None, 1 << 20).unwrap(),
+            hex("ee5fee5fee5ffa97")
+        );
+    }
+
+    #[test]
+    fn refinement_region() {
+        // An intermediate generic region refined into the page
+        let data = "\
+            00000000300001000000130000000c0000000800000000000000000000000000\
+            0001240001000000230000000a000000060000000100000001000003fffdff02fefefe\
+            c08b35cd816674ffac\
+            000000022a2001010000001f0000000a0000000600000001000000010000ffffffff\
+            f738286d67b87fffac\
+            0000000331000100000000";
+        assert_eq!(
+            decode(&hex(data), None, 1 << 20).unwrap(),
+            hex("ffff8fbffdffeb9fffbffdffffffffff")
+        );
+    }
+
+    #[test]
+    fn text_region() {
+        // Three symbols from a dictionary in the globals, placed four times
+        let globals = "\
+            000000000000010000001e000003fffdff02fefefe0000000300000003\
+            7c33d713158f7df54c67ffac";
+        let data = "\
+            0000000130000100000013000000140000000c00000000000000000000000000\
+            00020620000100000021000000140000000c0000000000000000000010000000\
+            04a2ef35c8216dd6a5ffac\
+            0000000331000100000000";
+        let globals = Object::Stream {
+            dict: parms(&[]),
+            data: Cow::Borrowed(&[]),
+            decoded: Some(Cow::Owned(hex(globals))),
+        };
+        let parms: Dict = [(Name::from("JBIG2Globals"), globals)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            decode(&hex(data), Some(&parms), 1 << 20).unwrap(),
+            hex("ffffffffffff7fffff7e7ffffc797ffc76fffff6ffffffffffffffffffffcbffffb7ffff")
+        );
+
+        // The symbols are missing without the globals
+        assert!(decode(&hex(data), None, 1 << 20).is_err());
+    }
+
+    #[test]
+    fn limit() {
+        // The page is 16 by 8
+        assert!(decode(&hex(GENERIC), None, 128).is_ok());
+        assert!(matches!(
+            decode(&hex(GENERIC), None, 127),
+            Err(FilterError::TooLarge { limit: 127, .. })
+        ));
+    }
+}
@@ -0,0 +1,291 @@
+//! The MQ arithmetic decoder (T.88 annex E) and the integer decoding procedures built on it
+//! (annex A).
+
+/// Qe value, next index after an MPS, next index after an LPS, and whether an LPS switches the
+/// meaning of MPS, for each probability estimation state.
+const QE: [(u32, u8, u8, bool); 47] = [
+    (0x5601, 1, 1, true),
+    (0x3401, 2, 6, false),
+    (0x1801, 3, 9, false),
+    (0x0AC1, 4, 12, false),
+    (0x0521, 5, 29, false),
+    (0x0221, 38, 33, false),
+    (0x5601, 7, 6, true),
+    (0x5401, 8, 14, false),
+    (0x4801, 9, 14, false),
+    (0x3801, 10, 14, false),
+    (0x3001, 11, 17, false),
+    (0x2401, 12, 18, false),
+    (0x1C01, 13, 20, false),
+    (0x1601, 29, 21, false),
+    (0x5601, 15, 14, true),
+    (0x5401, 16, 14, false),
+    (0x5101, 17, 15, false),
+    (0x4801, 18, 16, false),
+    (0x3801, 19, 17, false),
+    (0x3401, 20, 18, false),
+    (0x3001, 21, 19, false),
+    (0x2801, 22, 19, false),
+    (0x2401, 23, 20, false),
+    (0x2201, 24, 21, false),
+    (0x1C01, 25, 22, false),
+    (0x1801, 26, 23, false),
+    (0x1601, 27, 24, false),
+    (0x1401, 28, 25, false),
+    (0x1201, 29, 26, false),
+    (0x1101, 30, 27, false),
+    (0x0AC1, 31, 28, false),
+    (0x09C1, 32, 29, false),
+    (0x08A1, 33, 30, false),
+    (0x0521, 34, 31, false),
+    (0x0441, 35, 32, false),
+    (0x02A1, 36, 33, false),
+    (0x0221, 37, 34, false),
+    (0x0141, 38, 35, false),
+    (0x0111, 39, 36, false),
+    (0x0085, 40, 37, false),
+    (0x0049, 41, 38, false),
+    (0x0025, 42, 39, false),
+    (0x0015, 43, 40, false),
+    (0x0009, 44, 41, false),
+    (0x0005, 45, 42, false),
+    (0x0001, 45, 43, false),
+    (0x5601, 46, 46, false),
+];
+
+/// A context's state: its index into `QE` shifted left by one, with the MPS in the low bit.
+pub type Context = u8;
+
+pub struct MqDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    c: u32,
+    a: u32,
+    ct: u32,
+    /// How many bytes were made up past the end of the data.
+    overrun: usize,
+}
+
+impl<'a> MqDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        let mut decoder = Self {
+            data,
+            pos: 0,
+            c: 0,
+            a: 0x8000,
+            ct: 0,
+            overrun: 0,
+        };
+        decoder.c = (decoder.byte(0) as u32) << 16;
+        decoder.byte_in();
+        decoder.c <<= 7;
+        decoder.ct -= 7;
+        decoder
+    }
+
+    /// Whether decoding has gone well past the end of the data, which only corrupt data does.
+    pub fn overrun(&self) -> bool {
+        self.overrun > 16
+    }
+
+    /// Past the end, the data behaves as if it were followed by 0xFF bytes.
+    fn byte(&self, pos: usize) -> u8 {
+        self.data.get(pos).copied().unwrap_or(0xFF)
+    }
+
+    fn byte_in(&mut self) {
+        if self.byte(self.pos) == 0xFF {
+            if self.byte(self.pos + 1) > 0x8F {
+                // A marker: stop reading and feed 1 bits from here on
+                self.c += 0xFF00;
+                self.ct = 8;
+                self.overrun += 1;
+            } else {
+                self.pos += 1;
+                self.c += (self.byte(self.pos) as u32) << 9;
+                self.ct = 7;
+            }
+        } else {
+            self.pos += 1;
+            if self.pos >= self.data.len() {
+                self.overrun += 1;
+            }
+            self.c += (self.byte(self.pos) as u32) << 8;
+            self.ct = 8;
+        }
+    }
+
+    /// Decodes one bit in context `cx`, updating the context's probability estimate.
+    pub fn decode(&mut self, cx: &mut Context) -> u8 {
+        let (index, mps) = ((*cx >> 1) as usize, *cx & 1);
+        let (qe, nmps, nlps, switch) = QE[index];
+        let lps = |d: u8| nlps << 1 | if switch { d } else { mps };
+
+        self.a -= qe;
+        let d = if (self.c >> 16) < qe {
+            // LPS exchange
+            let d = if self.a < qe {
+                *cx = nmps << 1 | mps;
+                mps
+            } else {
+                *cx = lps(1 - mps);
+                1 - mps
+            };
+            self.a = qe;
+            d
+        } else {
+            self.c -= qe << 16;
+            if self.a & 0x8000 != 0 {
+                return mps;
+            }
+            // MPS exchange
+            if self.a < qe {
+                *cx = lps(1 - mps);
+                1 - mps
+            } else {
+                *cx = nmps << 1 | mps;
+                mps
+            }
+        };
+
+        while self.a & 0x8000 == 0 {
+            if self.ct == 0 {
+                self.byte_in();
+            }
+            self.a <<= 1;
+            self.c <<= 1;
+            self.ct -= 1;
+        }
+        d
+    }
+}
+
+/// One of the IAx integer decoders, each with its own set of contexts.
+pub struct IntDecoder {
+    contexts: Vec<Context>,
+}
+
+impl IntDecoder {
+    pub fn new() -> Self {
+        Self {
+            contexts: vec![0; 512],
+        }
+    }
+
+    /// Decodes an integer, or `None` for the out-of-band value.
+    pub fn decode(&mut self, mq: &mut MqDecoder) -> Option<i64> {
+        let mut prev = 1;
+        let sign = self.bits(mq, &mut prev, 1);
+        // A prefix of 1 bits says which range the value is in
+        let (len, offset) = if self.bits(mq, &mut prev, 1) == 0 {
+            (2, 0)
+        } else if self.bits(mq, &mut prev, 1) == 0 {
+            (4, 4)
+        } else if self.bits(mq, &mut prev, 1) == 0 {
+            (6, 20)
+        } else if self.bits(mq, &mut prev, 1) == 0 {
+            (8, 84)
+        } else if self.bits(mq, &mut prev, 1) == 0 {
+            (12, 340)
+        } else {
+            (32, 4436)
+        };
+        let value = self.bits(mq, &mut prev, len) as i64 + offset;
+
+        match (sign, value) {
+            (0, value) => Some(value),
+            (_, 0) => None,
+            (_, value) => Some(-value),
+        }
+    }
+
+    fn bits(&mut self, mq: &mut MqDecoder, prev: &mut usize, n: u32) -> u64 {
+        let mut value = 0;
+        for _ in 0..n {
+            let bit = mq.decode(&mut self.contexts[*prev]) as usize;
+            // The context is the last 8 bits decoded, with bit 8 set once there are that many
+            *prev = if *prev < 256 {
+                *prev << 1 | bit
+            } else {
+                ((*prev << 1 | bit) & 511) | 256
+            };
+            value = value << 1 | bit as u64;
+        }
+        value
+    }
+}
+
+/// The IAID decoder for symbol IDs, which are a fixed number of bits long.
+pub struct IdDecoder {
+    len: u32,
+    contexts: Vec<Context>,
+}
+
+impl IdDecoder {
+    pub fn new(len: u32) -> Self {
+        Self {
+            len,
+            contexts: vec![0; 1 << (len + 1)],
+        }
+    }
+
+    pub fn decode(&mut self, mq: &mut MqDecoder) -> usize {
+        let mut prev = 1;
+        for _ in 0..self.len {
+            prev = prev << 1 | mq.decode(&mut self.contexts[prev]) as usize;
+        }
+        prev - (1 << self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+
+    // The test sequence in T.88 annex H.2, all coded in one context
+    #[test]
+    fn test_sequence() {
+        let encoded = hex("84c73bfce1a1430402200000410dbb86f4317fff88ff37471adb6adfffac");
+        let mut mq = MqDecoder::new(&encoded);
+        let mut cx = 0;
+        let decoded: Vec<u8> = (0..32)
+            .map(|_| (0..8).fold(0, |byte, _| byte << 1 | mq.decode(&mut cx)))
+            .collect();
+        assert_eq!(
+            decoded,
+            hex("00020051000000c00352872aaaaaaaaa82c02000fcd79ef6bf7fed904f46a3bf")
+        );
+        assert!(!mq.overrun());
+    }
+
+    #[test]
+    fn integers() {
+        let encoded = hex("a0570739ba0ddf54b655d453fb045941b12f6733ce27ffac");
+        let mut mq = MqDecoder::new(&encoded);
+        let mut int = IntDecoder::new();
+        let expected = [
+            Some(0),
+            Some(3),
+            Some(-4),
+            Some(19),
+            Some(20),
+            Some(-83),
+            Some(84),
+            Some(339),
+            Some(340),
+            Some(4435),
+            Some(4436),
+            Some(-1000000),
+            None,
+            Some(7),
+        ];
+        for value in expected {
+            assert_eq!(int.decode(&mut mq), value);
+        }
+        let mut id = IdDecoder::new(3);
+        for value in [5, 0, 7, 2] {
+            assert_eq!(id.decode(&mut mq), value);
+        }
+    }
+}
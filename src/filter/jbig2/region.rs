@@ -0,0 +1,224 @@
+//! Generic region decoding (T.88 6.2) and generic refinement region decoding (6.3), both with
+//! arithmetic coding.
+
+use super::arith::{Context, MqDecoder};
+use super::{corrupt, Bitmap};
+use crate::filter::FilterError;
+
+/// A pixel of a context template, relative to the one being decoded.
+#[derive(Clone, Copy)]
+enum Pixel {
+    At(isize, isize),
+    /// The nth adaptive template pixel, whose position is given in the segment.
+    Adaptive(usize),
+}
+
+use Pixel::{Adaptive, At};
+
+/// The generic region templates, most significant context bit first.
+const GENERIC_TEMPLATES: [&[Pixel]; 4] = [
+    &[
+        Adaptive(3),
+        At(-1, -2),
+        At(0, -2),
+        At(1, -2),
+        Adaptive(2),
+        Adaptive(1),
+        At(-2, -1),
+        At(-1, -1),
+        At(0, -1),
+        At(1, -1),
+        At(2, -1),
+        Adaptive(0),
+        At(-4, 0),
+        At(-3, 0),
+        At(-2, 0),
+        At(-1, 0),
+    ],
+    &[
+        At(-1, -2),
+        At(0, -2),
+        At(1, -2),
+        At(2, -2),
+        At(-2, -1),
+        At(-1, -1),
+        At(0, -1),
+        At(1, -1),
+        At(2, -1),
+        Adaptive(0),
+        At(-3, 0),
+        At(-2, 0),
+        At(-1, 0),
+    ],
+    &[
+        At(-1, -2),
+        At(0, -2),
+        At(1, -2),
+        At(-2, -1),
+        At(-1, -1),
+        At(0, -1),
+        At(1, -1),
+        Adaptive(0),
+        At(-2, 0),
+        At(-1, 0),
+    ],
+    &[
+        At(-3, -1),
+        At(-2, -1),
+        At(-1, -1),
+        At(0, -1),
+        At(1, -1),
+        Adaptive(0),
+        At(-4, 0),
+        At(-3, 0),
+        At(-2, 0),
+        At(-1, 0),
+    ],
+];
+
+/// The context the SLTP bit of typical prediction is decoded in, for each template.
+const GENERIC_SLTP_CONTEXTS: [usize; 4] = [0x9B25, 0x0795, 0x00E5, 0x0195];
+
+/// The refinement templates: pixels of the bitmap being decoded, then pixels of the reference
+/// bitmap around the corresponding position, most significant context bit first.
+const REFINEMENT_TEMPLATES: [(&[Pixel], &[Pixel]); 2] = [
+    (
+        &[At(0, -1), At(1, -1), At(-1, 0), Adaptive(0)],
+        &[
+            At(0, -1),
+            At(1, -1),
+            At(-1, 0),
+            At(0, 0),
+            At(1, 0),
+            At(-1, 1),
+            At(0, 1),
+            At(1, 1),
+            Adaptive(1),
+        ],
+    ),
+    (
+        &[At(-1, -1), At(0, -1), At(1, -1), At(-1, 0)],
+        &[At(0, -1), At(-1, 0), At(0, 0), At(1, 0), At(0, 1), At(1, 1)],
+    ),
+];
+
+/// The SLTP contexts for refinement: the context with only the central reference pixel set.
+const REFINEMENT_SLTP_CONTEXTS: [usize; 2] = [0x0020, 0x0008];
+
+/// Enough contexts for any generic region template.
+pub const GENERIC_CONTEXTS: usize = 1 << 16;
+/// Enough contexts for any refinement template.
+pub const REFINEMENT_CONTEXTS: usize = 1 << 13;
+
+pub struct GenericParams {
+    pub template: usize,
+    /// Typical prediction: rows can be flagged as identical to the one above.
+    pub tpgdon: bool,
+    pub at: [(i8, i8); 4],
+    /// The most pixels the region may have.
+    pub limit: usize,
+}
+
+#[derive(Clone, Copy)]
+pub struct RefinementParams {
+    pub template: usize,
+    pub tpgron: bool,
+    pub at: [(i8, i8); 2],
+    pub limit: usize,
+}
+
+fn resolve(template: &[Pixel], at: &[(i8, i8)]) -> Vec<(isize, isize)> {
+    template
+        .iter()
+        .map(|&pixel| match pixel {
+            At(x, y) => (x, y),
+            Adaptive(n) => (at[n].0 as isize, at[n].1 as isize),
+        })
+        .collect()
+}
+
+fn context(bitmap: &Bitmap, pixels: &[(isize, isize)], x: isize, y: isize, cx: usize) -> usize {
+    pixels.iter().fold(cx, |cx, &(dx, dy)| {
+        cx << 1 | bitmap.get(x + dx, y + dy) as usize
+    })
+}
+
+pub fn decode_generic(
+    mq: &mut MqDecoder,
+    contexts: &mut [Context],
+    width: usize,
+    height: usize,
+    params: &GenericParams,
+) -> Result<Bitmap, FilterError> {
+    let pixels = resolve(GENERIC_TEMPLATES[params.template], &params.at);
+    let mut bitmap = Bitmap::new(width, height, 0, params.limit)?;
+
+    let mut ltp = false;
+    for y in 0..height {
+        if mq.overrun() {
+            return Err(corrupt("generic region data ends early"));
+        }
+        if params.tpgdon {
+            ltp ^= mq.decode(&mut contexts[GENERIC_SLTP_CONTEXTS[params.template]]) == 1;
+            if ltp {
+                if y > 0 {
+                    bitmap.copy_row(y - 1, y);
+                }
+                continue;
+            }
+        }
+        for x in 0..width {
+            let cx = context(&bitmap, &pixels, x as isize, y as isize, 0);
+            let bit = mq.decode(&mut contexts[cx]);
+            bitmap.set(x, y, bit);
+        }
+    }
+
+    Ok(bitmap)
+}
+
+/// Decodes a bitmap as a refinement of `reference`, which is offset by `(dx, dy)` from it.
+pub fn decode_refinement(
+    mq: &mut MqDecoder,
+    contexts: &mut [Context],
+    width: usize,
+    height: usize,
+    reference: &Bitmap,
+    (dx, dy): (isize, isize),
+    params: &RefinementParams,
+) -> Result<Bitmap, FilterError> {
+    let (coding, referenced) = REFINEMENT_TEMPLATES[params.template];
+    let coding = resolve(coding, &params.at);
+    let referenced = resolve(referenced, &params.at);
+    let mut bitmap = Bitmap::new(width, height, 0, params.limit)?;
+
+    let mut ltp = false;
+    for y in 0..height as isize {
+        if mq.overrun() {
+            return Err(corrupt("refinement region data ends early"));
+        }
+        if params.tpgron {
+            ltp ^= mq.decode(&mut contexts[REFINEMENT_SLTP_CONTEXTS[params.template]]) == 1;
+        }
+        for x in 0..width as isize {
+            let (rx, ry) = (x - dx, y - dy);
+            // With typical prediction, pixels in a uniform area of the reference are copied
+            if ltp {
+                let value = reference.get(rx, ry);
+                let uniform =
+                    (-1..=1).all(|j| (-1..=1).all(|i| reference.get(rx + i, ry + j) == value));
+                if uniform {
+                    bitmap.set(x as usize, y as usize, value);
+                    continue;
+                }
+            }
+
+            let cx = context(&bitmap, &coding, x, y, 0);
+            let cx = context(reference, &referenced, rx, ry, cx);
+            let bit = mq.decode(&mut contexts[cx]);
+            bitmap.set(x as usize, y as usize, bit);
+        }
+    }
+
+    Ok(bitmap)
+}
@@ -0,0 +1,293 @@
+//! Symbol dictionaries (T.88 6.5) and the text regions that place their symbols (6.4), with
+//! arithmetic coding.
+
+use super::arith::{IdDecoder, IntDecoder, MqDecoder};
+use super::region::{self, GenericParams, RefinementParams};
+use super::{corrupt, Bitmap, Combination};
+use crate::filter::FilterError;
+
+/// The integer decoders a text region uses. A symbol dictionary with refinement/aggregate
+/// coding shares them with the text regions it decodes its symbols as.
+pub struct TextDecoders {
+    dt: IntDecoder,
+    fs: IntDecoder,
+    ds: IntDecoder,
+    it: IntDecoder,
+    ri: IntDecoder,
+    rdw: IntDecoder,
+    rdh: IntDecoder,
+    rdx: IntDecoder,
+    rdy: IntDecoder,
+    id: IdDecoder,
+    /// Contexts for refinement decoding.
+    gr: Vec<u8>,
+}
+
+impl TextDecoders {
+    /// Decoders for a text region choosing from `num_symbols` symbols.
+    pub fn new(num_symbols: usize) -> Result<Self, FilterError> {
+        // ceil(log2(num_symbols)) bits per symbol ID
+        let id_len = usize::BITS - num_symbols.saturating_sub(1).leading_zeros();
+        if id_len > 24 {
+            return Err(corrupt("too many symbols"));
+        }
+        Ok(Self {
+            dt: IntDecoder::new(),
+            fs: IntDecoder::new(),
+            ds: IntDecoder::new(),
+            it: IntDecoder::new(),
+            ri: IntDecoder::new(),
+            rdw: IntDecoder::new(),
+            rdh: IntDecoder::new(),
+            rdx: IntDecoder::new(),
+            rdy: IntDecoder::new(),
+            id: IdDecoder::new(id_len),
+            gr: vec![0; region::REFINEMENT_CONTEXTS],
+        })
+    }
+}
+
+/// Decodes an integer where the out-of-band value isn't allowed.
+fn int(decoder: &mut IntDecoder, mq: &mut MqDecoder) -> Result<i64, FilterError> {
+    decoder
+        .decode(mq)
+        .ok_or_else(|| corrupt("unexpected out-of-band value"))
+}
+
+fn size(value: i64) -> Result<usize, FilterError> {
+    usize::try_from(value).map_err(|_| corrupt("negative symbol size"))
+}
+
+pub struct TextParams {
+    pub width: usize,
+    pub height: usize,
+    pub num_instances: usize,
+    /// Symbols are placed in strips of this many pixels (a power of 2).
+    pub strip_size: i64,
+    /// Which corner of a symbol its coordinates are for: bottom left, top left, bottom right
+    /// or top right.
+    pub ref_corner: u8,
+    /// Whether the strips are columns instead of rows.
+    pub transposed: bool,
+    pub combination: Combination,
+    pub default_pixel: u8,
+    pub ds_offset: i64,
+    pub refine: Option<RefinementParams>,
+    pub limit: usize,
+}
+
+pub fn decode_text_region(
+    mq: &mut MqDecoder,
+    decoders: &mut TextDecoders,
+    symbols: &[&Bitmap],
+    params: &TextParams,
+) -> Result<Bitmap, FilterError> {
+    let mut region = Bitmap::new(
+        params.width,
+        params.height,
+        params.default_pixel,
+        params.limit,
+    )?;
+    let d = decoders;
+    let right = params.ref_corner & 2 != 0;
+    let bottom = params.ref_corner & 1 == 0;
+
+    let mut strip_t = -int(&mut d.dt, mq)? * params.strip_size;
+    let mut first_s = 0;
+    let mut instances = 0;
+    while instances < params.num_instances {
+        strip_t += int(&mut d.dt, mq)? * params.strip_size;
+        first_s += int(&mut d.fs, mq)?;
+        let mut cur_s = first_s;
+
+        // The symbols of one strip
+        loop {
+            if mq.overrun() {
+                return Err(corrupt("text region data ends early"));
+            }
+            let cur_t = if params.strip_size > 1 {
+                int(&mut d.it, mq)?
+            } else {
+                0
+            };
+            let t = strip_t + cur_t;
+            let id = d.id.decode(mq);
+            let &symbol = symbols
+                .get(id)
+                .ok_or_else(|| corrupt(format!("symbol ID {} is out of range", id)))?;
+
+            let refined;
+            let symbol = match &params.refine {
+                Some(refine) if int(&mut d.ri, mq)? != 0 => {
+                    let rdw = int(&mut d.rdw, mq)?;
+                    let rdh = int(&mut d.rdh, mq)?;
+                    let rdx = int(&mut d.rdx, mq)?;
+                    let rdy = int(&mut d.rdy, mq)?;
+                    refined = region::decode_refinement(
+                        mq,
+                        &mut d.gr,
+                        size(symbol.width as i64 + rdw)?,
+                        size(symbol.height as i64 + rdh)?,
+                        symbol,
+                        (
+                            (rdw.div_euclid(2) + rdx) as isize,
+                            (rdh.div_euclid(2) + rdy) as isize,
+                        ),
+                        refine,
+                    )?;
+                    &refined
+                }
+                _ => symbol,
+            };
+
+            let (width, height) = (symbol.width as i64, symbol.height as i64);
+            // Coordinates are for the reference corner, and CURS moves along the strip past
+            // each symbol
+            match params.transposed {
+                false if right => cur_s += width - 1,
+                true if bottom => cur_s += height - 1,
+                _ => {}
+            }
+            let (x, y) = if params.transposed {
+                (t, cur_s)
+            } else {
+                (cur_s, t)
+            };
+            let left = if right { x - (width - 1) } else { x };
+            let top = if bottom { y - (height - 1) } else { y };
+            region.combine(symbol, left as isize, top as isize, params.combination);
+            match params.transposed {
+                false if !right => cur_s += width - 1,
+                true if !bottom => cur_s += height - 1,
+                _ => {}
+            }
+
+            instances += 1;
+            // A strip always ends with an out-of-band value, even the last one
+            match d.ds.decode(mq) {
+                Some(ds) => cur_s += ds + params.ds_offset,
+                None => break,
+            }
+        }
+    }
+
+    Ok(region)
+}
+
+pub struct SymbolDictParams {
+    /// Whether symbols are refinements or aggregates of other symbols rather than coded
+    /// directly.
+    pub refine_aggregate: bool,
+    pub generic: GenericParams,
+    pub refine: RefinementParams,
+    pub num_new: usize,
+}
+
+/// Decodes a symbol dictionary with `input` symbols from the dictionaries it refers to, and
+/// returns the symbols it exports.
+pub fn decode_symbol_dictionary(
+    mq: &mut MqDecoder,
+    input: &[&Bitmap],
+    params: &SymbolDictParams,
+) -> Result<Vec<Bitmap>, FilterError> {
+    let mut dh = IntDecoder::new();
+    let mut dw = IntDecoder::new();
+    let mut ex = IntDecoder::new();
+    let mut ai = IntDecoder::new();
+    let mut text = TextDecoders::new(input.len() + params.num_new)?;
+    let mut gb = vec![0; region::GENERIC_CONTEXTS];
+
+    // Symbols come in height classes, each a run of symbols of the same height
+    let mut new: Vec<Bitmap> = Vec::new();
+    let mut height = 0;
+    while new.len() < params.num_new {
+        if mq.overrun() {
+            return Err(corrupt("symbol dictionary data ends early"));
+        }
+        height += int(&mut dh, mq)?;
+        let mut width = 0;
+        while let Some(delta) = dw.decode(mq) {
+            width += delta;
+            if new.len() == params.num_new || mq.overrun() {
+                return Err(corrupt("symbol dictionary has more symbols than it says"));
+            }
+            let (width, height) = (size(width)?, size(height)?);
+
+            let symbol = if !params.refine_aggregate {
+                region::decode_generic(mq, &mut gb, width, height, &params.generic)?
+            } else {
+                let symbols: Vec<&Bitmap> = input.iter().copied().chain(&new).collect();
+                match int(&mut ai, mq)? {
+                    // A refinement of an existing symbol
+                    1 => {
+                        let id = text.id.decode(mq);
+                        let rdx = int(&mut text.rdx, mq)?;
+                        let rdy = int(&mut text.rdy, mq)?;
+                        let &reference = symbols
+                            .get(id)
+                            .ok_or_else(|| corrupt(format!("symbol ID {} is out of range", id)))?;
+                        region::decode_refinement(
+                            mq,
+                            &mut text.gr,
+                            width,
+                            height,
+                            reference,
+                            (rdx as isize, rdy as isize),
+                            &params.refine,
+                        )?
+                    }
+                    // An aggregate of several, coded like a text region
+                    n => decode_text_region(
+                        mq,
+                        &mut text,
+                        &symbols,
+                        &TextParams {
+                            width,
+                            height,
+                            num_instances: size(n)?,
+                            strip_size: 1,
+                            ref_corner: 1,
+                            transposed: false,
+                            combination: Combination::Or,
+                            default_pixel: 0,
+                            ds_offset: 0,
+                            refine: Some(params.refine),
+                            limit: params.generic.limit,
+                        },
+                    )?,
+                }
+            };
+            new.push(symbol);
+        }
+    }
+
+    // Which of the input and new symbols are exported is given as alternating runs of symbols
+    // that aren't and are
+    let total = input.len() + new.len();
+    let mut exported = Vec::new();
+    let mut index = 0;
+    let mut export = false;
+    while index < total {
+        if mq.overrun() {
+            return Err(corrupt("symbol dictionary data ends early"));
+        }
+        let run = int(&mut ex, mq)?;
+        let end = usize::try_from(run)
+            .ok()
+            .and_then(|run| index.checked_add(run))
+            .filter(|&end| end <= total)
+            .ok_or_else(|| corrupt("exported symbol run is out of range"))?;
+        if export {
+            for i in index..end {
+                exported.push(match i.checked_sub(input.len()) {
+                    Some(i) => new[i].clone(),
+                    None => input[i].clone(),
+                });
+            }
+        }
+        index = end;
+        export = !export;
+    }
+
+    Ok(exported)
+}
@@ -0,0 +1,122 @@
+//! `/JPXDecode`: JPEG 2000, either a JP2 file or a bare codestream. The data is passed through
+//! as it is, but the headers are read to find out what the image is.
+
+use super::{corrupt, FilterError, ImageInfo};
+
+const JP2_SIGNATURE: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x0C, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+];
+
+/// Whether `data` is a JP2 file, rather than just a codestream.
+pub fn is_jp2(data: &[u8]) -> bool {
+    data.starts_with(&JP2_SIGNATURE)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(pos..pos + 4)?.try_into().unwrap(),
+    ))
+}
+
+/// The contents of the first box of type `kind` among the boxes `data` is made of.
+fn find_box<'a>(mut data: &'a [u8], kind: &[u8; 4]) -> Result<Option<&'a [u8]>, FilterError> {
+    while !data.is_empty() {
+        let len = read_u32(data, 0).ok_or_else(|| corrupt("JPXDecode", "box header ends early"))?;
+        let (header_len, len) = match len {
+            // The box goes up to the end
+            0 => (8, data.len() as u64),
+            // The length is too large for 32 bits, and follows the type
+            1 => (
+                16,
+                data.get(8..16)
+                    .map(|len| u64::from_be_bytes(len.try_into().unwrap()))
+                    .ok_or_else(|| corrupt("JPXDecode", "box header ends early"))?,
+            ),
+            len => (8, len as u64),
+        };
+        if len < header_len || len > data.len() as u64 {
+            return Err(corrupt(
+                "JPXDecode",
+                format!("box is {} bytes long, which doesn't fit", len),
+            ));
+        }
+        if &data[4..8] == kind {
+            return Ok(Some(&data[header_len as usize..len as usize]));
+        }
+        data = &data[len as usize..];
+    }
+    Ok(None)
+}
+
+/// Reads the image size and format from the JP2 header box, or the codestream's SIZ marker
+/// segment if there's no JP2 header.
+pub fn probe(data: &[u8]) -> Result<ImageInfo, FilterError> {
+    if !is_jp2(data) {
+        return probe_codestream(data);
+    }
+
+    if let Some(header) = find_box(data, b"jp2h")? {
+        let ihdr = find_box(header, b"ihdr")?
+            .and_then(|ihdr| ihdr.get(..11))
+            .ok_or_else(|| corrupt("JPXDecode", "JP2 header has no image header box"))?;
+        let bits_per_component = match ihdr[10] {
+            // Components differ in bit depth, given in a box of their own
+            0xFF => find_box(header, b"bpcc")?
+                .ok_or_else(|| corrupt("JPXDecode", "bit depths vary, but there's no bpcc box"))?
+                .iter()
+                .map(|depth| (depth & 0x7F) + 1)
+                .max()
+                .unwrap_or(0),
+            // The high bit is for signed values
+            depth => (depth & 0x7F) + 1,
+        };
+        return Ok(ImageInfo {
+            width: read_u32(ihdr, 4).unwrap(),
+            height: read_u32(ihdr, 0).unwrap(),
+            components: u16::from_be_bytes([ihdr[8], ihdr[9]]),
+            bits_per_component,
+        });
+    }
+
+    let codestream = find_box(data, b"jp2c")?
+        .ok_or_else(|| corrupt("JPXDecode", "JP2 file has no header or codestream"))?;
+    probe_codestream(codestream)
+}
+
+fn probe_codestream(data: &[u8]) -> Result<ImageInfo, FilterError> {
+    if !data.starts_with(&[0xFF, 0x4F, 0xFF, 0x51]) {
+        return Err(corrupt(
+            "JPXDecode",
+            "data is neither a JP2 file nor a codestream starting with SOC and SIZ markers",
+        ));
+    }
+    let siz = &data[4..];
+    let field = |pos| read_u32(siz, pos).ok_or_else(|| corrupt("JPXDecode", "SIZ ends early"));
+    // The image area is offset within the reference grid
+    let (width, height) = (
+        field(4)?.checked_sub(field(12)?),
+        field(8)?.checked_sub(field(16)?),
+    );
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err(corrupt("JPXDecode", "image offset is past the image"));
+    };
+
+    let components = siz
+        .get(36..38)
+        .map(|count| u16::from_be_bytes([count[0], count[1]]))
+        .ok_or_else(|| corrupt("JPXDecode", "SIZ ends early"))?;
+    // Each component has a depth and subsampling factors
+    let depths = siz
+        .get(38..38 + 3 * components as usize)
+        .ok_or_else(|| corrupt("JPXDecode", "SIZ ends early"))?;
+    Ok(ImageInfo {
+        width,
+        height,
+        components,
+        bits_per_component: depths
+            .chunks(3)
+            .map(|component| (component[0] & 0x7F) + 1)
+            .max()
+            .unwrap_or(0),
+    })
+}
@@ -0,0 +1,182 @@
+//! `/LZWDecode`: the variable width (9 to 12 bit) LZW compression also used by TIFF.
+
+use super::{corrupt, too_large};
+
+const CLEAR: usize = 256;
+const EOD: usize = 257;
+const FIRST_CODE: usize = 258;
+const MAX_CODES: usize = 4096;
+
+/// Decompresses LZW data. With `early_change`, the code width grows one code earlier than
+/// strictly necessary, which is what `/EarlyChange 1` (the default) means.
+///
+/// Like Flate, data that stops without an end-of-data code is decoded as far as it goes, and
+/// data that decodes to more than `limit` bytes is rejected.
+pub fn decode(
+    data: &[u8],
+    early_change: bool,
+    limit: usize,
+) -> Result<Vec<u8>, super::FilterError> {
+    let mut out = Vec::with_capacity(data.len().saturating_mul(2).min(limit));
+    // Every table entry is a run of bytes that already appears in the output, so it's stored
+    // as the start and length of that run instead of copying the bytes around
+    let mut table: Vec<(usize, usize)> = Vec::new();
+    let mut prev: Option<(usize, usize)> = None;
+    let mut width = 9;
+    let (mut bit_buf, mut bit_count) = (0u32, 0u32);
+    let mut bytes = data.iter();
+
+    loop {
+        while bit_count < width {
+            let Some(&byte) = bytes.next() else {
+                return Ok(out);
+            };
+            bit_buf = (bit_buf << 8) | byte as u32;
+            bit_count += 8;
+        }
+        bit_count -= width;
+        let code = ((bit_buf >> bit_count) & ((1 << width) - 1)) as usize;
+
+        let start = out.len();
+        let cur = match code {
+            CLEAR => {
+                table.clear();
+                prev = None;
+                width = 9;
+                continue;
+            }
+            EOD => return Ok(out),
+            0..=255 => {
+                out.push(code as u8);
+                (start, 1)
+            }
+            _ => match (table.get(code - FIRST_CODE), prev) {
+                (Some(&(from, len)), _) => {
+                    out.extend_from_within(from..from + len);
+                    (start, len)
+                }
+                // The one code the encoder can send before the decoder has it in its table:
+                // the previous string followed by its own first byte
+                (None, Some((from, len))) if code - FIRST_CODE == table.len() => {
+                    out.extend_from_within(from..from + len);
+                    out.push(out[from]);
+                    (start, len + 1)
+                }
+                _ => return Err(corrupt("LZWDecode", format!("undefined code {}", code))),
+            },
+        };
+
+        // A code stands for at most `MAX_CODES` bytes, so this can't overshoot by much
+        if out.len() > limit {
+            return Err(too_large("LZWDecode", limit));
+        }
+
+        if let Some((from, len)) = prev {
+            if FIRST_CODE + table.len() < MAX_CODES {
+                table.push((from, len + 1));
+            }
+        }
+        prev = Some(cur);
+
+        width = match FIRST_CODE + table.len() + early_change as usize {
+            ..=511 => 9,
+            512..=1023 => 10,
+            1024..=2047 => 11,
+            _ => 12,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::crypt::hex;
+
+    /// LZW encodes `data`, growing the code width as `decode` expects, and checks that every
+    /// code fits in its width.
+    fn encode(data: &[u8], early_change: bool) -> Vec<u8> {
+        let mut table: HashMap<Vec<u8>, usize> = HashMap::new();
+        let code_of = |table: &HashMap<Vec<u8>, usize>, string: &[u8]| match string {
+            &[byte] => byte as usize,
+            _ => table[string],
+        };
+        let mut codes = vec![CLEAR];
+        let mut current = Vec::new();
+        for &byte in data {
+            current.push(byte);
+            if current.len() > 1 && !table.contains_key(&current) {
+                codes.push(code_of(&table, &current[..current.len() - 1]));
+                table.insert(current, FIRST_CODE + table.len());
+                current = vec![byte];
+            }
+        }
+        codes.push(code_of(&table, &current));
+        codes.push(EOD);
+        assert!(FIRST_CODE + table.len() < MAX_CODES);
+
+        let mut out = Vec::new();
+        let (mut bit_buf, mut bit_count) = (0u64, 0);
+        let mut width = 9;
+        for (i, &code) in codes.iter().enumerate() {
+            assert!(
+                code < 1 << width,
+                "code {} doesn't fit in {} bits",
+                code,
+                width
+            );
+            bit_buf = (bit_buf << width) | code as u64;
+            bit_count += width;
+            while bit_count >= 8 {
+                bit_count -= 8;
+                out.push((bit_buf >> bit_count) as u8);
+            }
+            // The decoder adds a table entry for every code but the first after a clear
+            let entries = i.saturating_sub(1);
+            width = match FIRST_CODE + entries + early_change as usize {
+                ..=511 => 9,
+                512..=1023 => 10,
+                1024..=2047 => 11,
+                _ => 12,
+            };
+        }
+        if bit_count > 0 {
+            out.push((bit_buf << (8 - bit_count)) as u8);
+        }
+        out
+    }
+
+    // The example in ISO 32000-1, 7.4.4.2
+    #[test]
+    fn spec_example() {
+        assert_eq!(
+            decode(&hex("800b6050220c0c8501"), true, 1 << 20).unwrap(),
+            b"-----A---B"
+        );
+    }
+
+    #[test]
+    fn early_change() {
+        // Enough distinct strings for the code width to reach 11 bits
+        let data: Vec<u8> = (0..6000u32).map(|i| (i * i % 251) as u8).collect();
+        for early_change in [false, true] {
+            let encoded = encode(&data, early_change);
+            assert_eq!(decode(&encoded, early_change, 1 << 20).unwrap(), data);
+            assert_ne!(
+                decode(&encoded, !early_change, 1 << 20).ok(),
+                Some(data.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn limit() {
+        let encoded = encode(&[0; 1000], true);
+        assert_eq!(decode(&encoded, true, 1000).unwrap(), vec![0; 1000]);
+        assert!(matches!(
+            decode(&encoded, true, 999),
+            Err(crate::filter::FilterError::TooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+//! Predictors (`/Predictor` in `/DecodeParms`), which LZW and Flate data can be filtered with
+//! before compression to make it compress better.
+
+use super::{int_parm, FilterError};
+use crate::Dict;
+
+/// Undoes the predictor described by `parms` (if any) on already decompressed data.
+pub fn unpredict(data: Vec<u8>, parms: Option<&Dict>) -> Result<Vec<u8>, FilterError> {
+    let predictor = int_parm(parms, "Predictor", 1);
+    if predictor == 1 {
+        return Ok(data);
+    }
+
+    let colors = int_parm(parms, "Colors", 1);
+    let bpc = int_parm(parms, "BitsPerComponent", 8);
+    let columns = int_parm(parms, "Columns", 1);
+    if !(1..=32).contains(&colors) || !matches!(bpc, 1 | 2 | 4 | 8 | 16) || columns < 1 {
+        return Err(FilterError::Corrupt {
+            filter: "predictor",
+            message: format!(
+                "invalid parameters: {} colours, {} bits per component, {} columns",
+                colors, bpc, columns
+            ),
+        });
+    }
+    let (colors, bpc, columns) = (colors as usize, bpc as usize, columns as usize);
+
+    if data.is_empty() {
+        return Ok(data);
+    }
+
+    // Checked before anything is allocated, since `/Columns` can be anything
+    let bits_per_pixel = colors * bpc;
+    let row_len = match columns.checked_mul(bits_per_pixel) {
+        Some(row_bits) if row_bits.div_ceil(8) <= data.len() => row_bits.div_ceil(8),
+        _ => {
+            return Err(FilterError::Corrupt {
+                filter: "predictor",
+                message: format!("{} columns is more than the data holds", columns),
+            });
+        }
+    };
+    match predictor {
+        2 => Ok(tiff(data, row_len, colors, bpc, columns * colors)),
+        10..=15 => png(&data, row_len, bits_per_pixel.div_ceil(8)),
+        _ => Err(FilterError::Unsupported(format!("predictor {}", predictor))),
+    }
+}
+
+/// TIFF predictor 2: every component is stored as the difference from the same component of
+/// the pixel to its left. Each row has `components` components, plus padding to a whole byte.
+fn tiff(
+    mut data: Vec<u8>,
+    row_len: usize,
+    colors: usize,
+    bpc: usize,
+    components: usize,
+) -> Vec<u8> {
+    for row in data.chunks_mut(row_len) {
+        match bpc {
+            8 => {
+                for i in colors..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - colors]);
+                }
+            }
+            16 => {
+                for i in (2 * colors..row.len() - row.len() % 2).step_by(2) {
+                    let prev = u16::from_be_bytes([row[i - 2 * colors], row[i - 2 * colors + 1]]);
+                    let cur = u16::from_be_bytes([row[i], row[i + 1]]);
+                    row[i..i + 2].copy_from_slice(&cur.wrapping_add(prev).to_be_bytes());
+                }
+            }
+            _ => {
+                // Components smaller than a byte: work on the bits directly, leaving the
+                // padding at the end of the row (or the missing end of the last one) alone
+                let mask = (1u8 << bpc) - 1;
+                let n_components = components.min(row.len() * 8 / bpc);
+                let get = |row: &[u8], i: usize| {
+                    let bit = i * bpc;
+                    (row[bit / 8] >> (8 - bpc - bit % 8)) & mask
+                };
+                for i in colors..n_components {
+                    let value = get(row, i).wrapping_add(get(row, i - colors)) & mask;
+                    let bit = i * bpc;
+                    let shift = 8 - bpc - bit % 8;
+                    row[bit / 8] = (row[bit / 8] & !(mask << shift)) | (value << shift);
+                }
+            }
+        }
+    }
+    data
+}
+
+/// PNG predictors: every row starts with a byte saying which PNG filter it was encoded with.
+/// `bpp` is the number of bytes per pixel, rounded up.
+fn png(data: &[u8], row_len: usize, bpp: usize) -> Result<Vec<u8>, FilterError> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut prev = vec![0u8; row_len];
+
+    for encoded in data.chunks(row_len + 1) {
+        let (&filter, encoded) = encoded.split_first().unwrap_or((&0, &[]));
+        let mut row = encoded.to_vec();
+        for i in 0..row.len() {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up = prev[i];
+            let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => {
+                    return Err(FilterError::Corrupt {
+                        filter: "predictor",
+                        message: format!("invalid PNG filter type {}", filter),
+                    });
+                }
+            };
+            row[i] = row[i].wrapping_add(predicted);
+        }
+
+        out.extend_from_slice(&row);
+        prev[..row.len()].copy_from_slice(&row);
+    }
+
+    Ok(out)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let pa = (p - left as i16).abs();
+    let pb = (p - up as i16).abs();
+    let pc = (p - up_left as i16).abs();
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+    use crate::filter::parms;
+
+    #[test]
+    fn png() {
+        // One row of each filter type, two bytes per pixel
+        let data = hex("000ac81e28fa3c010fbe1e65d8230255a6d3dc7bc103cfd00384c40104c708140a140a");
+        let parms = parms(&[("Predictor", 15), ("Colors", 2), ("Columns", 3)]);
+        assert_eq!(
+            unpredict(data, Some(&parms)).unwrap(),
+            hex("0ac81e28fa3c0fbe2d230546646400ff8007010203040506c80adc14f01e")
+        );
+    }
+
+    #[test]
+    fn tiff() {
+        let parms_8 = parms(&[("Predictor", 2), ("Colors", 3), ("Columns", 2)]);
+        assert_eq!(
+            unpredict(hex("0a141efbe605000000ff0180"), Some(&parms_8)).unwrap(),
+            hex("0a141e05fa23000000ff0180")
+        );
+
+        let parms_16 = parms(&[("Predictor", 2), ("BitsPerComponent", 16), ("Columns", 3)]);
+        assert_eq!(
+            unpredict(hex("03e8fa00021b00000001fffe"), Some(&parms_16)).unwrap(),
+            hex("03e8fde8000300000001ffff")
+        );
+
+        // Three 4 bit components a row, and the padding after them left as it is
+        let parms_4 = parms(&[("Predictor", 2), ("BitsPerComponent", 4), ("Columns", 3)]);
+        assert_eq!(
+            unpredict(hex("1e4a8085"), Some(&parms_4)).unwrap(),
+            hex("1f3a8805")
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let too_wide = parms(&[("Predictor", 12), ("Columns", 1 << 40)]);
+        assert!(unpredict(vec![0; 16], Some(&too_wide)).is_err());
+
+        let bad_filter = parms(&[("Predictor", 12), ("Columns", 2)]);
+        assert!(unpredict(hex("050102"), Some(&bad_filter)).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! `/RunLengthDecode`: the byte-oriented run length encoding from PackBits.
+
+use super::{corrupt, too_large, FilterError};
+
+/// Decodes runs up to the end-of-data byte (128). Each run starts with a length byte: below
+/// 128, that many plus one literal bytes follow; above it, the next byte is repeated 257 minus
+/// the length times. Data that decodes to more than `limit` bytes is rejected.
+pub fn decode(data: &[u8], limit: usize) -> Result<Vec<u8>, FilterError> {
+    let mut out = Vec::with_capacity(data.len().saturating_mul(2).min(limit));
+    let mut bytes = data.iter();
+
+    while let Some(&len) = bytes.next() {
+        match len {
+            0..=127 => {
+                let literal = bytes.as_slice().get(..len as usize + 1).ok_or_else(|| {
+                    corrupt(
+                        "RunLengthDecode",
+                        "literal run goes past the end of the data",
+                    )
+                })?;
+                out.extend_from_slice(literal);
+                bytes.nth(len as usize);
+            }
+            128 => break,
+            _ => {
+                let &byte = bytes.next().ok_or_else(|| {
+                    corrupt("RunLengthDecode", "repeated run has no byte to repeat")
+                })?;
+                out.resize(out.len() + 257 - len as usize, byte);
+            }
+        }
+        if out.len() > limit {
+            return Err(too_large("RunLengthDecode", limit));
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::hex;
+
+    // The PackBits example from Apple's technical note TN1023
+    #[test]
+    fn pack_bits() {
+        assert_eq!(
+            decode(&hex("feaa0280002afdaa0380002a22f7aa80ffee"), 1 << 20).unwrap(),
+            hex("aaaaaa80002aaaaaaaaa80002a22aaaaaaaaaaaaaaaaaaaa")
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(decode(&hex("0461"), 1 << 20).is_err());
+        assert!(decode(&hex("ff"), 1 << 20).is_err());
+        assert_eq!(
+            decode(&hex("8161"), 100),
+            Err(FilterError::TooLarge {
+                filter: "RunLengthDecode",
+                limit: 100
+            })
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! The document information dictionary, and the dates in it.
+
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::{text::decode_text_string, Document, Object, ParseError};
+
+/// A date, as written in a PDF file: `D:YYYYMMDDHHmmSSOHH'mm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// How far ahead of UT the time is, in minutes, or `None` if the date doesn't say.
+    pub utc_offset: Option<i16>,
+}
+
+impl Date {
+    /// Parses a date string. Everything after the year is optional, and so are the `D:`
+    /// and the apostrophes in the time zone, which files often leave out.
+    pub fn parse(text: &str) -> Option<Date> {
+        let text = text.trim_end_matches('\0').trim();
+        let mut rest = text.strip_prefix("D:").unwrap_or(text);
+
+        let year = chop_digits(&mut rest, 4)?;
+        // Month, day, hour, minute and second, each of which is only there if the last was
+        let mut fields = [1, 1, 0, 0, 0];
+        for field in &mut fields {
+            match chop_digits(&mut rest, 2) {
+                Some(value) => *field = value as u8,
+                None => break,
+            }
+        }
+        let [month, day, hour, minute, second] = fields;
+
+        let sign = match rest.chars().next() {
+            Some('Z') => Some(0),
+            Some('+') => Some(1),
+            Some('-') => Some(-1),
+            _ => None,
+        };
+        let utc_offset = match sign {
+            None => None,
+            Some(sign) => {
+                rest = &rest[1..];
+                let hours = chop_digits(&mut rest, 2).unwrap_or(0) as i16;
+                rest = rest.strip_prefix('\'').unwrap_or(rest);
+                let minutes = chop_digits(&mut rest, 2).unwrap_or(0) as i16;
+                rest = rest.strip_prefix('\'').unwrap_or(rest);
+                if hours > 23 || minutes > 59 {
+                    return None;
+                }
+                Some(sign * (hours * 60 + minutes))
+            }
+        };
+
+        let valid = (1..=12).contains(&month)
+            && (1..=31).contains(&day)
+            && hour < 24
+            && minute < 60
+            && second < 60;
+        (valid && rest.is_empty()).then_some(Date {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            utc_offset,
+        })
+    }
+
+    /// Parses an ISO 8601 date, the way XMP writes them: `YYYY-MM-DDThh:mm:ss.sTZD`, where
+    /// everything after the year is optional and the fraction of a second is ignored.
+    pub fn parse_iso8601(text: &str) -> Option<Date> {
+        let mut rest = text.trim();
+
+        let year = chop_digits(&mut rest, 4)?;
+        let mut fields = [1, 1, 0, 0, 0];
+        for (i, field) in fields.iter_mut().enumerate() {
+            let separator = ["-", "-", "T", ":", ":"][i];
+            let Some(after) = rest.strip_prefix(separator) else {
+                break;
+            };
+            rest = after;
+            *field = chop_digits(&mut rest, 2)? as u8;
+        }
+        let [month, day, hour, minute, second] = fields;
+        if let Some(fraction) = rest.strip_prefix('.') {
+            rest = fraction.trim_start_matches(|ch: char| ch.is_ascii_digit());
+        }
+
+        let utc_offset = match rest.chars().next() {
+            None => None,
+            Some('Z') => {
+                rest = &rest[1..];
+                Some(0)
+            }
+            Some(sign @ ('+' | '-')) => {
+                rest = &rest[1..];
+                let hours = chop_digits(&mut rest, 2)? as i16;
+                rest = rest.strip_prefix(':')?;
+                let minutes = chop_digits(&mut rest, 2)? as i16;
+                if hours > 23 || minutes > 59 {
+                    return None;
+                }
+                let offset = hours * 60 + minutes;
+                Some(if sign == '-' { -offset } else { offset })
+            }
+            Some(_) => return None,
+        };
+
+        let valid = (1..=12).contains(&month)
+            && (1..=31).contains(&day)
+            && hour < 24
+            && minute < 60
+            && second < 60;
+        (valid && rest.is_empty()).then_some(Date {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            utc_offset,
+        })
+    }
+
+    /// Whether the two dates are the same moment. If either doesn't give its time zone,
+    /// their local times are compared instead.
+    pub fn same_time(&self, other: &Date) -> bool {
+        match (self.utc_offset, other.utc_offset) {
+            (Some(a), Some(b)) => {
+                self.minutes() - a as i64 == other.minutes() - b as i64
+                    && self.second == other.second
+            }
+            _ => {
+                (
+                    self.year,
+                    self.month,
+                    self.day,
+                    self.hour,
+                    self.minute,
+                    self.second,
+                ) == (
+                    other.year,
+                    other.month,
+                    other.day,
+                    other.hour,
+                    other.minute,
+                    other.second,
+                )
+            }
+        }
+    }
+
+    /// Minutes since 1 March of year 0, in the proleptic Gregorian calendar.
+    fn minutes(&self) -> i64 {
+        let (month, day) = (self.month as i64, self.day as i64);
+        let year = self.year as i64 - (month <= 2) as i64;
+        let month = (month + 9) % 12;
+        let days = 365 * year + year / 4 - year / 100 + year / 400 + (153 * month + 2) / 5 + day;
+        (days * 24 + self.hour as i64) * 60 + self.minute as i64
+    }
+}
+
+/// Takes a number of exactly `len` digits off the start of `text`, if it starts with one.
+fn chop_digits(text: &mut &str, len: usize) -> Option<u16> {
+    let digits = text.get(..len)?;
+    if !digits.bytes().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    *text = &text[len..];
+    digits.parse().ok()
+}
+
+/// The date in ISO 8601 format, e.g. `2024-03-01T12:30:00+01:00`.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        match self.utc_offset {
+            None => Ok(()),
+            Some(0) => write!(f, "Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                write!(f, "{}{:02}:{:02}", sign, offset / 60, offset % 60)
+            }
+        }
+    }
+}
+
+/// The document information dictionary, the trailer's `/Info`, with its text strings
+/// decoded and its dates parsed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// The application that made the original document.
+    pub creator: Option<String>,
+    /// The application that converted it to PDF.
+    pub producer: Option<String>,
+    /// `None` if the date is missing, or isn't a valid date.
+    pub creation_date: Option<Date>,
+    pub mod_date: Option<Date>,
+    /// Whether the document has had trapping added: `True`, `False` or `Unknown`.
+    pub trapped: Option<String>,
+    /// Any other text entries, which applications can add as they please.
+    pub custom: BTreeMap<String, String>,
+}
+
+impl Document<'_> {
+    /// The document information dictionary, or `None` if there isn't one.
+    pub fn info(&mut self) -> Result<Option<DocumentInfo>, ParseError> {
+        let info = match self.trailer().get("Info") {
+            Some(info) => info.clone(),
+            None => return Ok(None),
+        };
+        let Object::Dict(dict) = self.deref(&info)?.clone() else {
+            return Ok(None);
+        };
+
+        let mut info = DocumentInfo::default();
+        for (key, value) in &dict {
+            let value = match self.deref(value)? {
+                Object::String(text) => decode_text_string(text),
+                Object::Name(name) => String::from_utf8_lossy(name.as_bytes()).into_owned(),
+                _ => continue,
+            };
+            let Some(key) = key.as_str() else {
+                continue;
+            };
+            match key {
+                "Title" => info.title = Some(value),
+                "Author" => info.author = Some(value),
+                "Subject" => info.subject = Some(value),
+                "Keywords" => info.keywords = Some(value),
+                "Creator" => info.creator = Some(value),
+                "Producer" => info.producer = Some(value),
+                "CreationDate" => info.creation_date = Date::parse(&value),
+                "ModDate" => info.mod_date = Date::parse(&value),
+                "Trapped" => info.trapped = Some(value),
+                _ => {
+                    info.custom.insert(key.to_owned(), value);
+                }
+            }
+        }
+        Ok(Some(info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(ymd: (u16, u8, u8), hms: (u8, u8, u8), utc_offset: Option<i16>) -> Date {
+        Date {
+            year: ymd.0,
+            month: ymd.1,
+            day: ymd.2,
+            hour: hms.0,
+            minute: hms.1,
+            second: hms.2,
+            utc_offset,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let full = date((2023, 4, 5), (6, 7, 8), Some(330));
+        assert_eq!(Date::parse("D:20230405060708+05'30'"), Some(full));
+        // The apostrophes are often left out, or only the last one is
+        assert_eq!(Date::parse("D:20230405060708+05'30"), Some(full));
+        assert_eq!(Date::parse("D:20230405060708+0530"), Some(full));
+        assert_eq!(Date::parse("20230405060708+05'30'"), Some(full));
+        assert_eq!(
+            Date::parse("D:20230405060708-08'"),
+            Some(date((2023, 4, 5), (6, 7, 8), Some(-480)))
+        );
+        assert_eq!(
+            Date::parse("D:20230405060708Z"),
+            Some(date((2023, 4, 5), (6, 7, 8), Some(0)))
+        );
+        assert_eq!(
+            Date::parse("D:20230405060708Z00'00'"),
+            Some(date((2023, 4, 5), (6, 7, 8), Some(0)))
+        );
+
+        // Everything after the year is optional
+        assert_eq!(
+            Date::parse("D:2023"),
+            Some(date((2023, 1, 1), (0, 0, 0), None))
+        );
+        assert_eq!(
+            Date::parse("D:202312"),
+            Some(date((2023, 12, 1), (0, 0, 0), None))
+        );
+        assert_eq!(
+            Date::parse("D:2023121509\0"),
+            Some(date((2023, 12, 15), (9, 0, 0), None))
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for text in [
+            "",
+            "D:",
+            "D:23",
+            "D:20231",
+            "D:20231301",
+            "D:20230100",
+            "D:20230132",
+            "D:2023010124",
+            "D:202301010060",
+            "D:20230101000060",
+            "D:20230101000000+24'00'",
+            "D:20230101000000+05'60'",
+            "D:20230101000000 junk",
+            "D:2023-01-01",
+        ] {
+            assert_eq!(Date::parse(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parse_iso8601() {
+        assert_eq!(
+            Date::parse_iso8601("2023-04-05T06:07:08.123+05:30"),
+            Some(date((2023, 4, 5), (6, 7, 8), Some(330)))
+        );
+        assert_eq!(
+            Date::parse_iso8601("2023-04-05T06:07Z"),
+            Some(date((2023, 4, 5), (6, 7, 0), Some(0)))
+        );
+        assert_eq!(
+            Date::parse_iso8601("2023"),
+            Some(date((2023, 1, 1), (0, 0, 0), None))
+        );
+        assert_eq!(
+            Date::parse_iso8601("2023-04"),
+            Some(date((2023, 4, 1), (0, 0, 0), None))
+        );
+        for text in ["2023-4-05", "2023-04-05T06:07+0530", "2023-02-28x"] {
+            assert_eq!(Date::parse_iso8601(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn same_time() {
+        // Across time zones, and days, years and a leap day
+        let utc = Date::parse("D:20240101003000Z").unwrap();
+        assert!(utc.same_time(&Date::parse("D:20231231233000-01'00'").unwrap()));
+        assert!(utc.same_time(&Date::parse_iso8601("2024-01-01T06:00:00+05:30").unwrap()));
+        assert!(!utc.same_time(&Date::parse("D:20240101003000+01'00'").unwrap()));
+        assert!(!utc.same_time(&Date::parse("D:20240101003001Z").unwrap()));
+        let leap = Date::parse("D:20240229230000-02'00'").unwrap();
+        assert!(leap.same_time(&Date::parse("D:20240301010000Z").unwrap()));
+
+        // Without a time zone, only the local time is compared
+        let local = Date::parse("D:20240101003000").unwrap();
+        assert!(local.same_time(&utc));
+        assert!(local.same_time(&Date::parse("D:20240101003000+09'00'").unwrap()));
+        assert!(!local.same_time(&Date::parse("D:20231231233000-01'00'").unwrap()));
+    }
+
+    #[test]
+    fn display() {
+        let date = Date::parse("D:20230405060708-08'30'").unwrap();
+        assert_eq!(date.to_string(), "2023-04-05T06:07:08-08:30");
+        assert_eq!(Date::parse_iso8601(&date.to_string()), Some(date));
+        assert_eq!(
+            Date::parse("D:2023Z").unwrap().to_string(),
+            "2023-01-01T00:00:00Z"
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! freed reads PDF files: it finds their objects through the cross-reference tables (or by
+//! scanning damaged files), decrypts and decodes them, and parses them on demand.
+//!
+//! Open a file with [`Document::open`], or parse one that's already in memory with
+//! [`Document::from_bytes`].
+
+pub mod crypt;
+mod document;
+pub mod filter;
+mod info;
+mod object;
+mod outline;
+mod page;
+mod parser;
+mod source;
+mod text;
+pub mod xml;
+mod xmp;
+
+pub use document::{Document, EncodedImage, Options};
+pub use info::{Date, DocumentInfo};
+pub use object::{Dict, Name, Object, ObjectId};
+pub use outline::{Destination, OutlineItem, View};
+pub use page::{Page, Rect};
+pub use parser::{ParseError, Revision, Version, XrefEntry};
+pub use text::{
+    decode_text_string, decode_text_string_with_languages, encode_text_string, LanguageTag,
+};
+pub use xmp::{Xmp, XmpValue};
@@ -0,0 +1,287 @@
+//! The objects a PDF file is made of.
+
+use core::fmt;
+use std::{
+    borrow::{Borrow, Cow},
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    str,
+};
+
+use crate::{parser::Parser, text::decode_text_string};
+
+/// A name object, like `/Type`. Names are bytes, and can hold anything but NUL with `#xx`
+/// escapes, although they're nearly always ASCII.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Name<'a>(Cow<'a, [u8]>);
+
+impl<'a> Name<'a> {
+    /// The name made of `bytes`, without the `/` and with any `#xx` escapes decoded.
+    pub fn new(bytes: impl Into<Cow<'a, [u8]>>) -> Self {
+        Name(bytes.into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The name as text, if it's UTF-8 like it's meant to be.
+    pub fn as_str(&self) -> Option<&str> {
+        str::from_utf8(&self.0).ok()
+    }
+
+    pub fn into_owned(self) -> Name<'static> {
+        Name(Cow::Owned(self.0.into_owned()))
+    }
+}
+
+impl<'a> From<&'a str> for Name<'a> {
+    fn from(name: &'a str) -> Self {
+        Name(Cow::Borrowed(name.as_bytes()))
+    }
+}
+
+impl Borrow<[u8]> for Name<'_> {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Name<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The name as PDF syntax, `/` and all, with whatever bytes need it escaped as `#xx`.
+/// Parsing this gives back the same name.
+impl fmt::Display for Name<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/")?;
+        for &byte in self.0.iter() {
+            if byte == b'#' || !Parser::is_ascii_normal(byte) || !byte.is_ascii_graphic() {
+                write!(f, "#{:02X}", byte)?;
+            } else {
+                write!(f, "{}", byte as char)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Name<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Some(name) => write!(f, "{:?}", name),
+            None => write!(f, "{}", self),
+        }
+    }
+}
+
+/// A dictionary object. Entries can be looked up by `&str`, or by any other bytes.
+#[derive(Clone, Default)]
+pub struct Dict<'a>(HashMap<Name<'a>, Object<'a>>);
+
+impl<'a> Dict<'a> {
+    /// See [`Object::into_owned`].
+    pub fn into_owned(self) -> Dict<'static> {
+        self.into_iter()
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect()
+    }
+
+    pub fn get(&self, key: &(impl AsRef<[u8]> + ?Sized)) -> Option<&Object<'a>> {
+        self.0.get(key.as_ref())
+    }
+
+    pub fn contains_key(&self, key: &(impl AsRef<[u8]> + ?Sized)) -> bool {
+        self.0.contains_key(key.as_ref())
+    }
+}
+
+impl<'a> Deref for Dict<'a> {
+    type Target = HashMap<Name<'a>, Object<'a>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Dict<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'a> FromIterator<(Name<'a>, Object<'a>)> for Dict<'a> {
+    fn from_iter<I: IntoIterator<Item = (Name<'a>, Object<'a>)>>(iter: I) -> Self {
+        Dict(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for Dict<'a> {
+    type Item = (Name<'a>, Object<'a>);
+    type IntoIter = std::collections::hash_map::IntoIter<Name<'a>, Object<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'d, 'a> IntoIterator for &'d Dict<'a> {
+    type Item = (&'d Name<'a>, &'d Object<'a>);
+    type IntoIter = std::collections::hash_map::Iter<'d, Name<'a>, Object<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl fmt::Debug for Dict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Clone)]
+pub enum Object<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(Vec<u8>),
+    Name(Name<'a>),
+    Array(Vec<Object<'a>>),
+    Dict(Dict<'a>),
+    Stream {
+        dict: Dict<'a>,
+        /// The stream's bytes exactly as they are in the file.
+        data: Cow<'a, [u8]>,
+        /// `data` with the stream's filters undone, or `None` if that failed.
+        decoded: Option<Cow<'a, [u8]>>,
+    },
+    Reference(ObjectId),
+}
+
+impl<'a> Object<'a> {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Object::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Object::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer or real number.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Object::Int(i) => Some(*i as f64),
+            Object::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Object::Name(name) => name.as_str(),
+            _ => None,
+        }
+    }
+
+    /// The object as a text string, decoded. See [`decode_text_string`].
+    pub fn as_text(&self) -> Option<String> {
+        match self {
+            Object::String(s) => Some(decode_text_string(s)),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&Dict<'a>> {
+        match self {
+            Object::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// This object, made independent of the buffer it was parsed from by copying whatever it
+    /// borrows. Owned objects can outlive the file's data, e.g. in a cache, or be built from
+    /// scratch to write out.
+    pub fn into_owned(self) -> Object<'static> {
+        match self {
+            Object::Null => Object::Null,
+            Object::Bool(b) => Object::Bool(b),
+            Object::Int(i) => Object::Int(i),
+            Object::Float(f) => Object::Float(f),
+            Object::String(s) => Object::String(s),
+            Object::Name(name) => Object::Name(name.into_owned()),
+            Object::Array(arr) => Object::Array(arr.into_iter().map(Object::into_owned).collect()),
+            Object::Dict(dict) => Object::Dict(dict.into_owned()),
+            Object::Stream {
+                dict,
+                data,
+                decoded,
+            } => Object::Stream {
+                dict: dict.into_owned(),
+                data: Cow::Owned(data.into_owned()),
+                decoded: decoded.map(|decoded| Cow::Owned(decoded.into_owned())),
+            },
+            Object::Reference(id) => Object::Reference(id),
+        }
+    }
+}
+
+impl fmt::Debug for Object<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Null => write!(f, "Null"),
+            Object::Bool(b) => write!(f, "Bool({})", b),
+            Object::Int(i) => write!(f, "Int({})", i),
+            Object::Float(fl) => write!(f, "Float({})", fl),
+            Object::String(s) if s.starts_with(b"\xFE\xFF") && s.len() % 2 == 0 => {
+                write!(f, "String(UTF-16 `{}`)", decode_text_string(s))
+            }
+            Object::String(s) => match String::from_utf8(s.clone()) {
+                Ok(string) => {
+                    write!(f, "String(`{}`)", string)
+                }
+                Err(_) => {
+                    write!(f, "String({:#02X?})", s)
+                }
+            },
+            Object::Name(name) => write!(f, "Name({})", name),
+            Object::Array(arr) => write!(f, "Array({:#?})", arr),
+            Object::Dict(dict) => write!(f, "Dict({:#?})", dict),
+            Object::Stream {
+                dict,
+                data,
+                decoded,
+            } => {
+                write!(f, "Stream(dict: {:#?}, data: {:#?}, decoded: ", dict, data)?;
+                match decoded {
+                    Some(decoded) => write!(f, "{} bytes)", decoded.len()),
+                    None => write!(f, "None)"),
+                }
+            }
+            Object::Reference(id) => write!(f, "Reference({})", id),
+        }
+    }
+}
+
+/// Identifies an indirect object: its object number and generation number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId {
+    pub num: usize,
+    pub gen: u16,
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} R", self.num, self.gen)
+    }
+}
@@ -0,0 +1,506 @@
+//! The document outline, also known as bookmarks: a tree of titled links into the document.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Dict, Document, Object, ObjectId, ParseError, Rect};
+
+/// How a destination shows its page.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum View {
+    /// `/XYZ`: `left`, `top` at the top left of the window, magnified by `zoom`. `None`
+    /// means to leave that as it is.
+    Xyz {
+        left: Option<f64>,
+        top: Option<f64>,
+        zoom: Option<f64>,
+    },
+    /// `/Fit`: the whole page fits in the window.
+    Fit,
+    /// `/FitH`: the page's width fits, with `top` at the top of the window.
+    FitH {
+        top: Option<f64>,
+    },
+    /// `/FitV`: the page's height fits, with `left` at the left of the window.
+    FitV {
+        left: Option<f64>,
+    },
+    /// `/FitR`: the rectangle fits in the window.
+    FitR(Rect),
+    /// `/FitB`, `/FitBH` and `/FitBV`: like `Fit`, `FitH` and `FitV`, but fitting the
+    /// bounding box of what's on the page rather than the whole page.
+    FitB,
+    FitBH {
+        top: Option<f64>,
+    },
+    FitBV {
+        left: Option<f64>,
+    },
+}
+
+/// Where in the document a link goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Destination {
+    /// The page's index in [`Document::pages`].
+    pub page: usize,
+    pub view: View,
+}
+
+/// An entry in the outline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutlineItem {
+    pub title: String,
+    /// Where the item goes, from its `/Dest` or its `/A` GoTo action, or `None` if it has
+    /// neither or it doesn't point at a page of this document.
+    pub destination: Option<Destination>,
+    /// The colour of the title, in DeviceRGB. Black unless `/C` says otherwise.
+    pub colour: [f64; 3],
+    pub italic: bool,
+    pub bold: bool,
+    /// Whether the item starts out expanded, showing its children.
+    pub open: bool,
+    pub children: Vec<OutlineItem>,
+}
+
+/// How many items an outline can have. Items can be shared between parents, and each
+/// parent gets its own copy, so a small file could otherwise make a huge outline.
+const MAX_ITEMS: usize = 1_000_000;
+
+/// The items of one level of the outline that have been read so far.
+struct Level {
+    items: Vec<OutlineItem>,
+    /// The ids of `items`.
+    ids: Vec<ObjectId>,
+    /// The item after the last one read, if there is one.
+    next: Option<ObjectId>,
+}
+
+impl<'a> Document<'a> {
+    /// The top-level items of the outline, each with its children, or an empty list if
+    /// there's no outline. If the page tree can't be read, the items have no destinations.
+    pub fn outline(&mut self) -> Result<Vec<OutlineItem>, ParseError> {
+        self.outline_up_to(MAX_ITEMS)
+    }
+
+    /// [`Document::outline`], failing if it has more than `max_items` items.
+    fn outline_up_to(&mut self, max_items: usize) -> Result<Vec<OutlineItem>, ParseError> {
+        let Some(outlines) = self.catalog()?.get("Outlines").cloned() else {
+            return Ok(Vec::new());
+        };
+        let Object::Dict(outlines) = self.deref(&outlines)?.clone() else {
+            return Ok(Vec::new());
+        };
+        let pages: HashMap<ObjectId, usize> = match self.pages() {
+            Ok(pages) => pages
+                .iter()
+                .enumerate()
+                .map(|(i, page)| (page.id, i))
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+        let mut named = None;
+
+        // The items on the way to the one being read: its ancestors, and the items before it
+        // in each of their lists. Only coming back to one of these is a cycle; an item can
+        // have more than one parent.
+        let mut path = HashSet::new();
+        let mut count = 0;
+        let mut levels = vec![Level {
+            items: Vec::new(),
+            ids: Vec::new(),
+            next: link(&outlines, "First"),
+        }];
+        loop {
+            let level = levels.last_mut().unwrap();
+            let Some(id) = level.next.take() else {
+                // Every item of this level has been read, so they're the children of the
+                // last item of the level above
+                let done = levels.pop().unwrap();
+                for id in &done.ids {
+                    path.remove(id);
+                }
+                match levels.last_mut() {
+                    Some(parent) => parent.items.last_mut().unwrap().children = done.items,
+                    None => return Ok(done.items),
+                }
+                continue;
+            };
+            if !path.insert(id) {
+                return Err(ParseError::ReferenceCycle { id });
+            }
+            count += 1;
+            if count > max_items {
+                return Err(ParseError::InvalidStructure(format!(
+                    "the outline has more than {} items",
+                    max_items
+                )));
+            }
+            let Object::Dict(dict) = self.get_object(id)? else {
+                return Err(ParseError::InvalidStructure(format!(
+                    "outline item {} isn't a dictionary",
+                    id
+                )));
+            };
+            let dict = dict.clone();
+            level.next = link(&dict, "Next");
+
+            let item = self.outline_item(&dict, &pages, &mut named)?;
+            let level = levels.last_mut().unwrap();
+            level.items.push(item);
+            level.ids.push(id);
+            if let Some(first) = link(&dict, "First") {
+                levels.push(Level {
+                    items: Vec::new(),
+                    ids: Vec::new(),
+                    next: Some(first),
+                });
+            }
+        }
+    }
+
+    /// The item `dict`, without its children.
+    fn outline_item(
+        &mut self,
+        dict: &Dict<'a>,
+        pages: &HashMap<ObjectId, usize>,
+        named: &mut Option<HashMap<Vec<u8>, Object<'a>>>,
+    ) -> Result<OutlineItem, ParseError> {
+        let title = match dict.get("Title") {
+            Some(title) => self.deref(title)?.as_text().unwrap_or_default(),
+            None => String::new(),
+        };
+
+        // A `/Dest` takes precedence; only GoTo actions go anywhere in this document
+        let mut target = match dict.get("Dest") {
+            Some(dest) => Some(self.deref(dest)?.clone()),
+            None => None,
+        };
+        if target.is_none() {
+            if let Some(action) = dict.get("A") {
+                if let Object::Dict(action) = self.deref(action)?.clone() {
+                    if action.get("S").and_then(Object::as_name) == Some("GoTo") {
+                        if let Some(dest) = action.get("D") {
+                            target = Some(self.deref(dest)?.clone());
+                        }
+                    }
+                }
+            }
+        }
+        let destination = match target {
+            Some(target) => self.destination(&target, pages, named)?,
+            None => None,
+        };
+
+        let mut colour = [0.0; 3];
+        if let Some(c) = dict.get("C") {
+            if let Object::Array(c) = self.deref(c)? {
+                let components: Vec<f64> = c.iter().filter_map(Object::as_number).collect();
+                if let [r, g, b] = components[..] {
+                    colour = [r, g, b].map(|component| component.clamp(0.0, 1.0));
+                }
+            }
+        }
+        let flags = match dict.get("F") {
+            Some(flags) => self.deref(flags)?.as_int().unwrap_or(0),
+            None => 0,
+        };
+        let count = match dict.get("Count") {
+            Some(count) => self.deref(count)?.as_int().unwrap_or(0),
+            None => 0,
+        };
+
+        Ok(OutlineItem {
+            title,
+            destination,
+            colour,
+            italic: flags & 1 != 0,
+            bold: flags & 2 != 0,
+            open: count > 0,
+            children: Vec::new(),
+        })
+    }
+
+    /// Resolves a destination: an array of a page and a view, or the name of one. Named
+    /// destinations are looked up in `named`, which is read the first time it's needed.
+    fn destination(
+        &mut self,
+        dest: &Object<'a>,
+        pages: &HashMap<ObjectId, usize>,
+        named: &mut Option<HashMap<Vec<u8>, Object<'a>>>,
+    ) -> Result<Option<Destination>, ParseError> {
+        let dest = match dest {
+            Object::Name(_) | Object::String(_) => {
+                if named.is_none() {
+                    *named = Some(self.named_destinations()?);
+                }
+                let key = match dest {
+                    Object::Name(name) => name.as_bytes(),
+                    Object::String(s) => s.as_slice(),
+                    _ => unreachable!(),
+                };
+                let Some(value) = named.as_ref().unwrap().get(key).cloned() else {
+                    return Ok(None);
+                };
+                // Which is either the array itself, or a dictionary with it in `/D`
+                match self.deref(&value)?.clone() {
+                    Object::Dict(dict) => match dict.get("D") {
+                        Some(dest) => self.deref(dest)?.clone(),
+                        None => return Ok(None),
+                    },
+                    dest => dest,
+                }
+            }
+            dest => dest.clone(),
+        };
+        let Object::Array(array) = dest else {
+            return Ok(None);
+        };
+
+        // The page is normally a reference to it, but some files give its index instead
+        let page = match array.first() {
+            Some(&Object::Reference(id)) => pages.get(&id).copied(),
+            Some(&Object::Int(index)) => usize::try_from(index)
+                .ok()
+                .filter(|&index| index < pages.len()),
+            _ => None,
+        };
+        let Some(page) = page else {
+            return Ok(None);
+        };
+
+        let mut params = Vec::new();
+        for param in array.iter().skip(2) {
+            params.push(self.deref(param)?.as_number());
+        }
+        let param = |i: usize| params.get(i).copied().flatten();
+        let view = match array.get(1).and_then(Object::as_name) {
+            Some("XYZ") => View::Xyz {
+                left: param(0),
+                top: param(1),
+                // A zoom of 0 means the same as null
+                zoom: param(2).filter(|&zoom| zoom != 0.0),
+            },
+            Some("FitH") => View::FitH { top: param(0) },
+            Some("FitV") => View::FitV { left: param(0) },
+            Some("FitR") => match (param(0), param(1), param(2), param(3)) {
+                (Some(left), Some(bottom), Some(right), Some(top)) => {
+                    View::FitR(Rect::new(left, bottom, right, top))
+                }
+                _ => View::Fit,
+            },
+            Some("FitB") => View::FitB,
+            Some("FitBH") => View::FitBH { top: param(0) },
+            Some("FitBV") => View::FitBV { left: param(0) },
+            // `/Fit`, or a view that doesn't make sense, which might as well be `/Fit`
+            _ => View::Fit,
+        };
+        Ok(Some(Destination { page, view }))
+    }
+
+    /// Every named destination: those in the catalog's `/Dests` dictionary (PDF 1.1), keyed
+    /// by name, and those in the `/Dests` name tree, keyed by string.
+    fn named_destinations(&mut self) -> Result<HashMap<Vec<u8>, Object<'a>>, ParseError> {
+        let catalog = self.catalog()?.clone();
+        let mut named = HashMap::new();
+
+        if let Some(dests) = catalog.get("Dests") {
+            if let Object::Dict(dests) = self.deref(dests)? {
+                for (name, value) in dests {
+                    named.insert(name.as_bytes().to_vec(), value.clone());
+                }
+            }
+        }
+
+        let tree = match catalog.get("Names") {
+            Some(names) => match self.deref(names)? {
+                Object::Dict(names) => names.get("Dests").cloned(),
+                _ => None,
+            },
+            None => None,
+        };
+        // The name tree's nodes that are left to read
+        let mut nodes: Vec<Object<'a>> = tree.into_iter().collect();
+        let mut visited = HashSet::new();
+        while let Some(node) = nodes.pop() {
+            if let Object::Reference(id) = node {
+                if !visited.insert(id) {
+                    return Err(ParseError::ReferenceCycle { id });
+                }
+            }
+            let Object::Dict(node) = self.deref(&node)?.clone() else {
+                continue;
+            };
+            if let Some(kids) = node.get("Kids") {
+                if let Object::Array(kids) = self.deref(kids)? {
+                    nodes.extend(kids.iter().cloned());
+                }
+            }
+            if let Some(names) = node.get("Names") {
+                if let Object::Array(names) = self.deref(names)?.clone() {
+                    for pair in names.chunks_exact(2) {
+                        if let Object::String(key) = self.deref(&pair[0])? {
+                            named.insert(key.clone(), pair[1].clone());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(named)
+    }
+}
+
+/// The outline item that `dict`'s `key` links to.
+fn link(dict: &Dict, key: &str) -> Option<ObjectId> {
+    match dict.get(key) {
+        Some(&Object::Reference(id)) => Some(id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::file;
+
+    const CATALOG: &str = "<< /Type /Catalog /Pages 2 0 R /Outlines 5 0 R \
+                           /Dests << /old [4 0 R /Fit] >> /Names << /Dests 12 0 R >> >>";
+    const PAGES: [&str; 3] = [
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 /MediaBox [0 0 612 792] >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+    ];
+    /// Items 6, 7 and 8, with 6 and 7 sharing the children 9 and 10.
+    const ITEMS: [&str; 5] = [
+        "<< /Title (One) /Dest [3 0 R /XYZ 10 20 0] /Next 7 0 R /First 9 0 R /Last 10 0 R \
+         /Count 2 /C [1 0.5 2] /F 3 >>",
+        "<< /Title (Two) /A << /S /GoTo /D /old >> /Next 8 0 R /First 9 0 R /Last 10 0 R >>",
+        "<< /Title (Three) /Dest (new) >>",
+        "<< /Title (Child) /Dest [1 /FitH 50] /Next 10 0 R >>",
+        "<< /Title (Last child) /Dest [7 /Fit] >>",
+    ];
+    const NAME_TREE: [&str; 2] = [
+        "<< /Names [(new) << /D [4 0 R /FitR 1 2 3 4] >>] >>",
+        "<< /Kids [11 0 R] >>",
+    ];
+
+    fn outline(catalog: &str, items: &[&str]) -> Result<Vec<OutlineItem>, ParseError> {
+        let mut objects = vec![catalog];
+        objects.extend(PAGES);
+        objects.push("<< /Type /Outlines /First 6 0 R /Last 8 0 R >>");
+        objects.extend(items);
+        objects.extend(NAME_TREE);
+        let file = file(&objects, "/Root 1 0 R");
+        crate::Document::from_bytes(&file).unwrap().outline()
+    }
+
+    fn item(title: &str, destination: Option<Destination>) -> OutlineItem {
+        OutlineItem {
+            title: title.to_owned(),
+            destination,
+            colour: [0.0; 3],
+            italic: false,
+            bold: false,
+            open: false,
+            children: Vec::new(),
+        }
+    }
+
+    fn dest(page: usize, view: View) -> Option<Destination> {
+        Some(Destination { page, view })
+    }
+
+    #[test]
+    fn outline_tree() {
+        // A page index instead of a reference, if the page exists
+        let children = vec![
+            item("Child", dest(1, View::FitH { top: Some(50.0) })),
+            item("Last child", None),
+        ];
+        let one = OutlineItem {
+            colour: [1.0, 0.5, 1.0],
+            italic: true,
+            bold: true,
+            open: true,
+            children: children.clone(),
+            ..item(
+                "One",
+                dest(
+                    0,
+                    View::Xyz {
+                        left: Some(10.0),
+                        top: Some(20.0),
+                        zoom: None,
+                    },
+                ),
+            )
+        };
+        // Named destinations, from the catalog's `/Dests` and from the name tree
+        let two = OutlineItem {
+            children,
+            ..item("Two", dest(1, View::Fit))
+        };
+        let three = item("Three", dest(1, View::FitR(Rect::new(1.0, 2.0, 3.0, 4.0))));
+        assert_eq!(outline(CATALOG, &ITEMS).unwrap(), [one, two, three]);
+    }
+
+    #[test]
+    fn without_pages() {
+        let catalog = CATALOG.replace("/Pages 2 0 R", "/Pages 99 0 R");
+        let outline = outline(&catalog, &ITEMS).unwrap();
+        let titles: Vec<_> = outline.iter().map(|item| item.title.as_str()).collect();
+        assert_eq!(titles, ["One", "Two", "Three"]);
+        assert!(outline.iter().all(|item| item.destination.is_none()));
+        assert_eq!(outline[1].children.len(), 2);
+    }
+
+    #[test]
+    fn cycle() {
+        let mut items = ITEMS;
+        items[2] = "<< /Title (Three) /Next 7 0 R >>";
+        assert!(matches!(
+            outline(CATALOG, &items),
+            Err(ParseError::ReferenceCycle { id }) if id == ObjectId { num: 7, gen: 0 }
+        ));
+
+        // A child that's its own parent
+        let mut items = ITEMS;
+        items[4] = "<< /Title (Last child) /First 6 0 R >>";
+        assert!(matches!(
+            outline(CATALOG, &items),
+            Err(ParseError::ReferenceCycle { id }) if id == ObjectId { num: 6, gen: 0 }
+        ));
+    }
+
+    #[test]
+    fn too_many_items() {
+        // 8 levels of two items, where both items of each level share the next level as
+        // their children, so the outline doubles in size at each level: 510 items in all
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R /Outlines 3 0 R >>".to_owned(),
+            "<< /Type /Pages /Kids [] /Count 0 >>".to_owned(),
+            "<< /First 4 0 R >>".to_owned(),
+        ];
+        for level in 0..8 {
+            let first = 4 + 2 * level;
+            let children = match level {
+                7 => String::new(),
+                _ => format!("/First {} 0 R", first + 2),
+            };
+            objects.push(format!(
+                "<< /Title (a) /Next {} 0 R {} >>",
+                first + 1,
+                children
+            ));
+            objects.push(format!("<< /Title (b) {} >>", children));
+        }
+        let objects: Vec<_> = objects.iter().map(String::as_str).collect();
+        let file = file(&objects, "/Root 1 0 R");
+        let mut doc = crate::Document::from_bytes(&file).unwrap();
+
+        assert_eq!(doc.outline_up_to(510).unwrap().len(), 2);
+        assert!(matches!(
+            doc.outline_up_to(509),
+            Err(ParseError::InvalidStructure(message))
+                if message == "the outline has more than 509 items"
+        ));
+    }
+}
@@ -0,0 +1,306 @@
+//! The page tree, and the pages in it.
+
+use core::fmt;
+use std::collections::HashSet;
+
+use crate::{Dict, Document, Name, Object, ObjectId, ParseError};
+
+/// The attributes that pages inherit from their ancestors in the page tree, unless they have
+/// their own.
+const INHERITABLE: [&str; 4] = ["Resources", "MediaBox", "CropBox", "Rotate"];
+
+/// How many millimetres there are in a point, which is 1/72 inch.
+const MM_PER_POINT: f64 = 25.4 / 72.0;
+
+/// A rectangle, such as a page boundary, in default user space units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub left: f64,
+    pub bottom: f64,
+    pub right: f64,
+    pub top: f64,
+}
+
+impl Rect {
+    /// US Letter, for pages without a usable `/MediaBox`.
+    pub const LETTER: Rect = Rect {
+        left: 0.0,
+        bottom: 0.0,
+        right: 612.0,
+        top: 792.0,
+    };
+
+    /// The rectangle with corners `(x1, y1)` and `(x2, y2)`, which can be either pair of
+    /// opposite corners.
+    pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        Self {
+            left: x1.min(x2),
+            bottom: y1.min(y2),
+            right: x1.max(x2),
+            top: y1.max(y2),
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> f64 {
+        self.top - self.bottom
+    }
+
+    /// The part of this rectangle that's inside `other`, or `None` if there's nothing left.
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect {
+            left: self.left.max(other.left),
+            bottom: self.bottom.max(other.bottom),
+            right: self.right.min(other.right),
+            top: self.top.min(other.top),
+        };
+        (rect.width() > 0.0 && rect.height() > 0.0).then_some(rect)
+    }
+}
+
+/// The rectangle as a PDF array, e.g. `[0 0 612 792]`.
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{} {} {} {}]",
+            self.left, self.bottom, self.right, self.top
+        )
+    }
+}
+
+/// A page, with the attributes it inherits from the page tree filled in, and the defaults
+/// for the ones it doesn't have.
+#[derive(Clone, Debug)]
+pub struct Page<'a> {
+    pub id: ObjectId,
+    /// The page object itself, without any inherited attributes.
+    pub dict: Dict<'a>,
+    /// `/Resources`, or an empty dictionary if the page has none.
+    pub resources: Dict<'a>,
+    /// The medium the page is meant to be printed on. Pages are meant to have one, but those
+    /// that don't (or whose box is empty) are assumed to be US Letter.
+    pub media_box: Rect,
+    /// The part of the page that's shown or printed. Defaults to the media box.
+    pub crop_box: Rect,
+    /// Where the page is clipped to in production, bleed included. Defaults to the crop box.
+    pub bleed_box: Rect,
+    /// The size of the finished page, once it's trimmed. Defaults to the crop box.
+    pub trim_box: Rect,
+    /// The page's meaningful content. Defaults to the crop box.
+    pub art_box: Rect,
+    /// `/Rotate`, in degrees clockwise: 0, 90, 180 or 270.
+    pub rotate: i64,
+    /// How big a unit of user space is, in points.
+    pub user_unit: f64,
+}
+
+impl Page<'_> {
+    /// The (width, height) the page is shown at in points: the size of the crop box, scaled
+    /// by `/UserUnit` and turned by `/Rotate`.
+    pub fn size(&self) -> (f64, f64) {
+        let width = self.crop_box.width() * self.user_unit;
+        let height = self.crop_box.height() * self.user_unit;
+        match self.rotate {
+            90 | 270 => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// [`Page::size`] in millimetres.
+    pub fn size_mm(&self) -> (f64, f64) {
+        let (width, height) = self.size();
+        (width * MM_PER_POINT, height * MM_PER_POINT)
+    }
+}
+
+/// A page tree node on the way down to the page being looked at.
+struct Node<'a> {
+    id: ObjectId,
+    /// The inheritable attributes the node has, or inherits.
+    inherited: Dict<'a>,
+    kids: Vec<ObjectId>,
+    /// The next kid to look at.
+    next: usize,
+}
+
+impl<'a> Document<'a> {
+    /// Every page in the document, in order, found by walking the page tree.
+    pub fn pages(&mut self) -> Result<Vec<Page<'a>>, ParseError> {
+        let root = match self.catalog()?.get("Pages") {
+            Some(&Object::Reference(id)) => id,
+            _ => {
+                return Err(ParseError::InvalidStructure(
+                    "catalog has no `/Pages` reference".to_owned(),
+                ))
+            }
+        };
+
+        let mut pages = Vec::new();
+        let mut visited = HashSet::new();
+        let mut path: Vec<Node<'a>> = Vec::new();
+        let mut next = Some((self.follow(root)?, Dict::default()));
+        loop {
+            if let Some((id, mut inherited)) = next.take() {
+                if !visited.insert(id) {
+                    if path.iter().any(|node| node.id == id) {
+                        return Err(ParseError::ReferenceCycle { id });
+                    }
+                    return Err(ParseError::InvalidStructure(format!(
+                        "object {} is in the page tree more than once",
+                        id
+                    )));
+                }
+                let Object::Dict(dict) = self.get_object(id)? else {
+                    return Err(ParseError::InvalidStructure(format!(
+                        "page tree node {} isn't a dictionary",
+                        id
+                    )));
+                };
+                let dict = dict.clone();
+                for key in INHERITABLE {
+                    if let Some(value) = dict.get(key) {
+                        inherited.insert(Name::from(key), value.clone());
+                    }
+                }
+
+                // Some files leave out `/Type`, but only intermediate nodes have `/Kids`
+                let is_node = match dict.get("Type").and_then(Object::as_name) {
+                    Some(kind) => kind == "Pages",
+                    None => dict.contains_key("Kids"),
+                };
+                if is_node {
+                    let kids = self.kids(id, &dict)?;
+                    path.push(Node {
+                        id,
+                        inherited,
+                        kids,
+                        next: 0,
+                    });
+                } else {
+                    pages.push(self.page(id, dict, &inherited)?);
+                }
+            }
+
+            // Carry on with the next kid of the deepest node that has any left
+            let Some(node) = path.last_mut() else {
+                break;
+            };
+            match node.kids.get(node.next) {
+                Some(&kid) => {
+                    node.next += 1;
+                    next = Some((kid, node.inherited.clone()));
+                }
+                None => {
+                    path.pop();
+                }
+            }
+        }
+        Ok(pages)
+    }
+
+    /// How many pages there are. This walks the page tree rather than trusting `/Count`.
+    pub fn page_count(&mut self) -> Result<usize, ParseError> {
+        Ok(self.pages()?.len())
+    }
+
+    /// The ids of the kids of page tree node `id`.
+    fn kids(&mut self, id: ObjectId, node: &Dict<'a>) -> Result<Vec<ObjectId>, ParseError> {
+        let kids = match node.get("Kids") {
+            Some(kids) => self.deref(kids)?.clone(),
+            None => Object::Array(Vec::new()),
+        };
+        let Object::Array(kids) = kids else {
+            return Err(ParseError::InvalidStructure(format!(
+                "`/Kids` of page tree node {} isn't an array",
+                id
+            )));
+        };
+        kids.iter()
+            .map(|kid| match *kid {
+                Object::Reference(kid) => self.follow(kid),
+                _ => Err(ParseError::InvalidStructure(format!(
+                    "page tree node {} has a kid that isn't a reference",
+                    id
+                ))),
+            })
+            .collect()
+    }
+
+    /// Page `id`, given its own and its inherited attributes.
+    fn page(
+        &mut self,
+        id: ObjectId,
+        dict: Dict<'a>,
+        attributes: &Dict<'a>,
+    ) -> Result<Page<'a>, ParseError> {
+        let resources = match attributes.get("Resources") {
+            Some(resources) => self.deref(resources)?.as_dict().cloned(),
+            None => None,
+        };
+        // Only multiples of 90 are allowed, so anything else is ignored
+        let rotate = match attributes.get("Rotate") {
+            Some(rotate) => self.deref(rotate)?.as_int(),
+            None => None,
+        };
+        let rotate = rotate.filter(|rotate| rotate % 90 == 0).unwrap_or(0);
+        let user_unit = match dict.get("UserUnit") {
+            Some(user_unit) => self.deref(user_unit)?.as_number(),
+            None => None,
+        };
+        let user_unit = user_unit.filter(|&unit| unit > 0.0 && unit.is_finite());
+
+        let media_box = self.rect_entry(attributes, "MediaBox")?;
+        let media_box = media_box
+            .filter(|media_box| media_box.width() > 0.0 && media_box.height() > 0.0)
+            .unwrap_or(Rect::LETTER);
+        // The other boxes are cut down to the media box, and default to their parent box if
+        // they're missing or end up empty
+        let clip = |rect: Option<Rect>, default: Rect| {
+            rect.and_then(|rect| rect.intersect(&media_box))
+                .unwrap_or(default)
+        };
+        let crop_box = clip(self.rect_entry(attributes, "CropBox")?, media_box);
+        let bleed_box = clip(self.rect_entry(&dict, "BleedBox")?, crop_box);
+        let trim_box = clip(self.rect_entry(&dict, "TrimBox")?, crop_box);
+        let art_box = clip(self.rect_entry(&dict, "ArtBox")?, crop_box);
+
+        Ok(Page {
+            id,
+            dict,
+            resources: resources.unwrap_or_default(),
+            media_box,
+            crop_box,
+            bleed_box,
+            trim_box,
+            art_box,
+            rotate: rotate.rem_euclid(360),
+            user_unit: user_unit.unwrap_or(1.0),
+        })
+    }
+
+    /// The rectangle `key` of `dict`, or `None` if it's missing or isn't a rectangle.
+    fn rect_entry(&mut self, dict: &Dict<'a>, key: &str) -> Result<Option<Rect>, ParseError> {
+        let array = match dict.get(key) {
+            Some(array) => self.deref(array)?.clone(),
+            None => return Ok(None),
+        };
+        let Object::Array(array) = array else {
+            return Ok(None);
+        };
+        let mut corners = Vec::with_capacity(4);
+        for value in &array {
+            match self.deref(value)?.as_number() {
+                Some(value) => corners.push(value),
+                None => return Ok(None),
+            }
+        }
+        match corners[..] {
+            [x1, y1, x2, y2] => Ok(Some(Rect::new(x1, y1, x2, y2))),
+            _ => Ok(None),
+        }
+    }
+}
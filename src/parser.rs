@@ -0,0 +1,2430 @@
+//! Reading objects out of a PDF file, and finding them through its cross-reference tables.
+
+use core::fmt;
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    ops::Range,
+    str::{self, FromStr},
+};
+
+use crate::{
+    crypt,
+    document::Options,
+    filter,
+    object::{Dict, Name, Object, ObjectId},
+    source::Source,
+    xml,
+};
+
+/// The PDF version in a file's header, e.g. `Version(1, 7)` for `%PDF-1.7`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Version(pub u8, pub u8);
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        let Self(smaj, smin) = self;
+        let Self(omaj, omin) = other;
+        if smaj == omaj {
+            if smin == omin {
+                Some(Ordering::Equal)
+            } else if smin > omin {
+                Some(Ordering::Greater)
+            } else {
+                Some(Ordering::Less)
+            }
+        } else if smaj > omaj {
+            Some(Ordering::Greater)
+        } else {
+            Some(Ordering::Less)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Keyword {
+    R,
+    Xref,
+    Trailer,
+    EntryInUse,
+    EntryFree,
+    Obj,
+    EndObj,
+    Stream,
+    EndStream,
+    True,
+    False,
+    Null,
+}
+
+impl FromStr for Keyword {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "R" => Ok(Keyword::R),
+            "xref" => Ok(Keyword::Xref),
+            "trailer" => Ok(Keyword::Trailer),
+            "n" => Ok(Keyword::EntryInUse),
+            "f" => Ok(Keyword::EntryFree),
+
+            "obj" => Ok(Keyword::Obj),
+            "endobj" => Ok(Keyword::EndObj),
+
+            "stream" => Ok(Keyword::Stream),
+            "endstream" => Ok(Keyword::EndStream),
+
+            "true" => Ok(Keyword::True),
+            "false" => Ok(Keyword::False),
+
+            "null" => Ok(Keyword::Null),
+
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Token {
+    ArrayBegin,
+    ArrayEnd,
+
+    DictBegin,
+    DictEnd,
+
+    Solidus,
+
+    Float(f64),
+    Int(i64),
+
+    String(Vec<u8>),
+
+    Keyword(Keyword),
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::ArrayBegin => write!(f, "ArrayBegin"),
+            Token::ArrayEnd => write!(f, "ArrayEnd"),
+            Token::DictBegin => write!(f, "DictBegin"),
+            Token::DictEnd => write!(f, "DictEnd"),
+            Token::Solidus => write!(f, "Solidus"),
+
+            Token::Float(fl) => {
+                write!(f, "Float({})", fl)
+            }
+            Token::Int(i) => {
+                write!(f, "Int({})", i)
+            }
+
+            Token::String(data) => match String::from_utf8(data.clone()) {
+                Ok(s) => {
+                    write!(f, "String(`{s}`)")
+                }
+                Err(_) => {
+                    write!(f, "String({data:#02X?})")
+                }
+            },
+
+            Token::Keyword(kw) => {
+                write!(f, "Keyword({:?})", kw)
+            }
+        }
+    }
+}
+
+/// Where the object with a given number lives, as listed in a cross-reference section.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum XrefEntry {
+    /// A deleted object, linking to the next free object number.
+    Free { next: usize, gen: u16 },
+    /// An object stored directly in the file at byte offset `offset`.
+    InUse { offset: usize, gen: u16 },
+    /// The `index`th object inside the object stream with object number `stream`.
+    Compressed { stream: usize, index: usize },
+}
+
+/// A single cross-reference section and its trailer: the original file, or one update to it.
+#[derive(Clone, Debug)]
+pub struct Revision<'a> {
+    /// Byte offset of the `xref` keyword or cross-reference stream.
+    pub xref_offset: usize,
+    pub trailer: Dict<'a>,
+    /// Only the objects this revision added, changed or deleted.
+    pub entries: HashMap<usize, XrefEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// Something other than what the grammar requires was found at `offset`.
+    UnexpectedToken {
+        offset: usize,
+        expected: &'static str,
+        found: String,
+        context: String,
+    },
+    /// The input ended before `expected` could be found.
+    UnexpectedEof {
+        offset: usize,
+        expected: &'static str,
+    },
+    /// The syntax is fine, but a value makes no sense (e.g. a negative `/Length`).
+    InvalidValue {
+        offset: usize,
+        message: String,
+        context: String,
+    },
+    /// A stream's data couldn't be decoded.
+    Filter {
+        offset: usize,
+        error: filter::FilterError,
+    },
+    /// Following references (or parsing an object) led back to where it started.
+    ReferenceCycle { id: ObjectId },
+    /// The file is encrypted, and couldn't be decrypted.
+    Crypt(crypt::CryptError),
+    /// Every object parses, but they don't fit together the way a document's should, e.g.
+    /// the trailer has no `/Root`.
+    InvalidStructure(String),
+    /// The XMP metadata isn't well-formed XML.
+    Xml(xml::XmlError),
+    /// The file couldn't be read.
+    Io {
+        kind: io::ErrorKind,
+        message: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                offset,
+                expected,
+                found,
+                context,
+            } => write!(
+                f,
+                "index {}: expected {}, found {} (near {:?})",
+                offset, expected, found, context
+            ),
+            ParseError::UnexpectedEof { offset, expected } => {
+                write!(
+                    f,
+                    "index {}: unexpected end of file, expected {}",
+                    offset, expected
+                )
+            }
+            ParseError::InvalidValue {
+                offset,
+                message,
+                context,
+            } => write!(f, "index {}: {} (near {:?})", offset, message, context),
+            ParseError::Filter { offset, error } => write!(f, "index {}: {}", offset, error),
+            ParseError::ReferenceCycle { id } => {
+                write!(f, "reference cycle through object {}", id)
+            }
+            ParseError::Crypt(error) => write!(f, "{}", error),
+            ParseError::InvalidStructure(message) => write!(f, "{}", message),
+            ParseError::Xml(error) => write!(f, "XMP metadata: {}", error),
+            ParseError::Io { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Parser<'a> {
+    data: Source<'a>,
+    start: usize,
+    end: usize,
+    cur: usize,
+
+    pub(crate) version: Version,
+
+    pub(crate) trailer_dict: Dict<'a>,
+    /// The merged cross-reference table of the latest revision, including free entries.
+    xref_table: HashMap<usize, XrefEntry>,
+    /// Objects that have been parsed so far. See [`Parser::resolve`].
+    objects: HashMap<ObjectId, Object<'a>>,
+    /// Objects currently being parsed, to catch e.g. a stream whose `/Length` is itself.
+    resolving: HashSet<ObjectId>,
+    /// Every xref section in the file, oldest first. Later ones are incremental updates.
+    pub(crate) revisions: Vec<Revision<'a>>,
+    /// Parsed headers of object streams, keyed by their object number.
+    /// Each entry is an (object number, offset into the stream data) pair.
+    object_streams: HashMap<usize, Vec<(usize, usize)>>,
+    /// Where every `num gen obj` in the file is, found by [`Parser::scan_for_objects`].
+    /// Only filled in once an xref table turns out to be wrong.
+    scanned_objects: Option<HashMap<usize, XrefEntry>>,
+    /// If the file was too damaged to read normally, the error that made us rebuild the xref
+    /// table from scratch.
+    pub(crate) recovered_from: Option<ParseError>,
+    /// Decrypts objects as they're parsed, if the file is encrypted.
+    pub(crate) security: Option<crypt::SecurityHandler>,
+    /// The `/Encrypt` dictionary, if it's an indirect object. Its strings aren't encrypted.
+    encrypt_id: Option<ObjectId>,
+    /// Whether to record comments in `comments` as they're skipped.
+    keep_comments: bool,
+    /// The most bytes any one filter may decode a stream to.
+    max_decoded_len: usize,
+    /// The text of each `%` comment the parser has come across, minus the `%` and the end of
+    /// the line, keyed by the comment's offset.
+    pub(crate) comments: BTreeMap<usize, Cow<'a, [u8]>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Number of bytes shown on either side of the offending offset in error messages.
+    const CONTEXT_RADIUS: usize = 16;
+
+    /// Parses the file `data`, decrypting it if it's encrypted.
+    pub(crate) fn new(data: Source<'a>, options: &Options) -> Result<Self, ParseError> {
+        let mut ret = Self::over(data.clone());
+        ret.keep_comments = options.keep_comments;
+        ret.max_decoded_len = options.max_decoded_len.unwrap_or(filter::MAX_DECODED_LEN);
+        if let Err(err) = ret.init() {
+            // Reading the file failed, rather than the file being damaged
+            if let Some(err) = ret.data.take_error() {
+                return Err(err.into());
+            }
+            ret = Self::over(data);
+            ret.keep_comments = options.keep_comments;
+            ret.max_decoded_len = options.max_decoded_len.unwrap_or(filter::MAX_DECODED_LEN);
+            ret.recover().map_err(|err| ret.read_error(err))?;
+            ret.recovered_from = Some(err);
+        }
+        ret.init_security(options.password)?;
+        Ok(ret)
+    }
+
+    /// A parser over a bare fragment of PDF syntax, such as the contents of an object stream.
+    /// Unlike [`Parser::new`], this doesn't look for a header, trailer or xref table.
+    fn over(data: impl Into<Source<'a>>) -> Self {
+        let data = data.into();
+        Self {
+            start: 0,
+            end: data.len(),
+            cur: 0,
+
+            version: Version(0, 0),
+            data,
+
+            trailer_dict: Dict::default(),
+            xref_table: HashMap::new(),
+            objects: HashMap::new(),
+            resolving: HashSet::new(),
+            revisions: Vec::new(),
+            object_streams: HashMap::new(),
+            scanned_objects: None,
+            recovered_from: None,
+            security: None,
+            encrypt_id: None,
+            keep_comments: false,
+            max_decoded_len: filter::MAX_DECODED_LEN,
+            comments: BTreeMap::new(),
+        }
+    }
+
+    fn init(&mut self) -> Result<(), ParseError> {
+        // Set start, version
+        self.find_forwards(b"%PDF-", "`%PDF-` header")?;
+        self.start = self.cur;
+        self.cur += 5;
+        let vmaj = self.chop_int::<u8>("version number after `%PDF-`")?;
+        self.expect_char(b'.', "`.` in version number")?;
+        let vmin = self.chop_int::<u8>("version number after `%PDF-`")?;
+        self.version = Version(vmaj, vmin);
+        // Usually followed by a comment of bytes above 127, to mark the file as binary
+        self.skip_whitespace();
+
+        // TODO: is Parser::end necessary?
+        // Set end
+        self.cur = self.data.len();
+        self.find_backwards(b"%%EOF", "`%%EOF` marker")?;
+        self.end = self.cur;
+        self.cur = self.cur.saturating_sub(1);
+        if !matches!(self.chop_char_backwards(), Some(b'\n')) {
+            let found = self.data.get(self.cur).unwrap_or_default();
+            return Err(self.unexpected("newline before `%%EOF` marker", found));
+        }
+
+        // Get xref table offset
+        while self.cur > 0
+            && self
+                .data
+                .get(self.cur - 1)
+                .is_some_and(|ch| ch.is_ascii_digit())
+        {
+            self.chop_char_backwards();
+        }
+        let xref_offset =
+            self.chop_int::<usize>("offset to xref table immediately before `%%EOF`")?;
+
+        self.seek(xref_offset)?;
+        self.fill_xref_table()
+    }
+
+    /// Rebuilds the xref table of a damaged file by scanning it for objects, and finds or
+    /// makes up a trailer for it.
+    fn recover(&mut self) -> Result<(), ParseError> {
+        if self.find_forwards(b"%PDF-", "`%PDF-` header").is_ok() {
+            self.start = self.cur;
+            self.cur += 5;
+            if let (Ok(vmaj), Ok(()), Ok(vmin)) = (
+                self.chop_int::<u8>("version number after `%PDF-`"),
+                self.expect_char(b'.', "`.` in version number"),
+                self.chop_int::<u8>("version number after `%PDF-`"),
+            ) {
+                self.version = Version(vmaj, vmin);
+                self.skip_whitespace();
+            }
+        }
+        self.end = self.data.len();
+
+        self.xref_table = self.scanned_objects().clone();
+        self.list_compressed_objects();
+        self.trailer_dict = self.recover_trailer()?;
+        Ok(())
+    }
+
+    /// Adds the objects in object streams to a rebuilt xref table, since they don't show up
+    /// in the scan.
+    fn list_compressed_objects(&mut self) {
+        for id in self.object_ids() {
+            let is_object_stream = matches!(
+                self.resolve(id),
+                Ok(Object::Stream { dict, .. })
+                    if dict.get("Type").and_then(Object::as_name) == Some("ObjStm")
+            );
+            if !is_object_stream || self.load_object_stream(id.num).is_err() {
+                continue;
+            }
+            for (index, &(num, _)) in self.object_streams[&id.num].iter().enumerate() {
+                self.xref_table.entry(num).or_insert(XrefEntry::Compressed {
+                    stream: id.num,
+                    index,
+                });
+            }
+        }
+    }
+
+    /// Sets up decryption if the trailer has an `/Encrypt` dictionary. Anything parsed while
+    /// reading the xref table is thrown away, since it was parsed without decrypting.
+    fn init_security(&mut self, password: &[u8]) -> Result<(), ParseError> {
+        let encrypt = match self.trailer_dict.get("Encrypt") {
+            None | Some(Object::Null) => return Ok(()),
+            Some(&Object::Reference(id)) => {
+                self.encrypt_id = Some(id);
+                self.resolve(id)?.clone()
+            }
+            Some(encrypt) => encrypt.clone(),
+        };
+        let Object::Dict(encrypt) = encrypt else {
+            return Err(self.invalid("`Encrypt` must be a dictionary"));
+        };
+        // Files without an `/ID` can still be decrypted, as if it were empty
+        let file_id = match self.trailer_dict.get("ID") {
+            Some(Object::Array(ids)) => match ids.first() {
+                Some(Object::String(id)) => id.clone(),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+
+        let security =
+            crypt::SecurityHandler::new(&encrypt, &file_id, password).map_err(ParseError::Crypt)?;
+        self.security = Some(security);
+        self.objects.clear();
+        self.object_streams.clear();
+        if self.recovered_from.is_some() {
+            self.list_compressed_objects();
+        }
+        Ok(())
+    }
+
+    /// The newest trailer dictionary or cross-reference stream that points to a catalog, or
+    /// failing that, a new trailer made for whichever object looks like the catalog.
+    fn recover_trailer(&mut self) -> Result<Dict<'a>, ParseError> {
+        self.cur = self.data.len();
+        while self.find_backwards(b"trailer", "`trailer` keyword").is_ok() {
+            let begin = self.cur;
+            self.cur += b"trailer".len();
+            if let Ok(trailer) = self.chop_dict_obj() {
+                if trailer.contains_key("Root") {
+                    return Ok(trailer);
+                }
+            }
+            if begin == 0 {
+                break;
+            }
+            self.cur = begin - 1;
+        }
+
+        // Newest first, i.e. the ones furthest into the file
+        let mut ids = self
+            .xref_table
+            .iter()
+            .filter_map(|(&num, entry)| match *entry {
+                XrefEntry::InUse { offset, gen } => Some((offset, ObjectId { num, gen })),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+
+        for &(_, id) in &ids {
+            if let Ok(Object::Stream { dict, .. }) = self.resolve(id) {
+                if dict.get("Type").and_then(Object::as_name) == Some("XRef")
+                    && dict.contains_key("Root")
+                {
+                    return Ok(dict.clone());
+                }
+            }
+        }
+
+        for id in self.object_ids().into_iter().rev() {
+            if let Ok(Object::Dict(dict)) = self.resolve(id) {
+                if dict.get("Type").and_then(Object::as_name) == Some("Catalog") {
+                    let size = self.xref_table.keys().max().map_or(0, |&last| last + 1);
+                    return Ok(Dict::from_iter([
+                        (Name::from("Root"), Object::Reference(id)),
+                        (Name::from("Size"), Object::Int(size as i64)),
+                    ]));
+                }
+            }
+        }
+
+        Err(ParseError::InvalidValue {
+            offset: 0,
+            message: "couldn't find a trailer or document catalog in damaged file".to_owned(),
+            context: self.context(0),
+        })
+    }
+
+    /// Where every `num gen obj` in the file is, scanning the whole file the first time.
+    fn scanned_objects(&mut self) -> &HashMap<usize, XrefEntry> {
+        if self.scanned_objects.is_none() {
+            self.scanned_objects = Some(self.scan_for_objects());
+        }
+        self.scanned_objects.as_ref().unwrap()
+    }
+
+    /// Finds every `num gen obj` in the file. When an object appears more than once, the last
+    /// one wins, since incremental updates are appended to the end of the file.
+    fn scan_for_objects(&self) -> HashMap<usize, XrefEntry> {
+        let mut objects = HashMap::new();
+        let data = &self.data;
+        let byte_before = |i: usize| data.get(i - 1).unwrap_or_default();
+
+        let mut pos = 0;
+        while let Some(obj_at) = data.find(b"obj", pos) {
+            pos = obj_at + 3;
+            if data.get(pos).is_some_and(Self::is_ascii_normal) {
+                continue;
+            }
+
+            // Walk backwards over `num gen `
+            let mut i = obj_at;
+            let mut numbers = [0usize; 2];
+            let mut valid = true;
+            for number in numbers.iter_mut().rev() {
+                let ws_end = i;
+                while i > 0 && Self::is_ascii_whitespace(byte_before(i)) {
+                    i -= 1;
+                }
+                let digits_end = i;
+                while i > 0 && byte_before(i).is_ascii_digit() {
+                    i -= 1;
+                }
+                let digits = data.bytes(i..digits_end);
+                match str::from_utf8(&digits).ok().map(str::parse) {
+                    Some(Ok(n)) if ws_end > digits_end => *number = n,
+                    _ => valid = false,
+                }
+            }
+            let at_boundary = i == 0 || !Self::is_ascii_normal(byte_before(i));
+            let [num, gen] = numbers;
+            if valid && at_boundary && gen <= u16::MAX as usize {
+                objects.insert(
+                    num,
+                    XrefEntry::InUse {
+                        offset: i,
+                        gen: gen as u16,
+                    },
+                );
+            }
+        }
+
+        objects
+    }
+
+    fn fill_xref_table(&mut self) -> Result<(), ParseError> {
+        // Walk the `/Prev` chain from the newest xref section back to the original one
+        let mut revisions = Vec::new();
+        let mut visited = HashSet::new();
+        let mut next = Some(self.cur);
+        while let Some(offset) = next {
+            if !visited.insert(offset) {
+                return Err(self.invalid("`Prev` chain of xref sections loops back on itself"));
+            }
+            self.seek(offset)?;
+            let revision = self.chop_revision()?;
+            next = match revision.trailer.get("Prev") {
+                None => None,
+                Some(Object::Int(prev)) if *prev >= 0 => Some(*prev as usize),
+                Some(_) => {
+                    return Err(self.invalid("`Prev` in trailer must be a non-negative integer"));
+                }
+            };
+            revisions.push(revision);
+        }
+        revisions.reverse();
+
+        // Later revisions override whatever earlier ones said about an object
+        let mut entries = HashMap::new();
+        for revision in &revisions {
+            entries.extend(revision.entries.iter().map(|(&nref, &entry)| (nref, entry)));
+        }
+        if let Some(newest) = revisions.last() {
+            self.trailer_dict = newest.trailer.clone();
+        }
+        self.revisions = revisions;
+        self.xref_table = entries;
+
+        Ok(())
+    }
+
+    /// Parses the xref section at the cursor, along with the trailer that belongs to it.
+    fn chop_revision(&mut self) -> Result<Revision<'a>, ParseError> {
+        match self.peek_token()? {
+            Token::Keyword(Keyword::Xref) => {
+                let mut revision = self.chop_xref_section()?;
+                // Hybrid-reference files hide objects from pre-1.5 readers in an xref stream
+                if let Some(Object::Int(stm_offset)) = revision.trailer.get("XRefStm") {
+                    self.seek(usize::try_from(*stm_offset).unwrap_or(usize::MAX))?;
+                    for (nref, entry) in self.chop_xref_stream()?.entries {
+                        if !matches!(revision.entries.get(&nref), Some(XrefEntry::InUse { .. })) {
+                            revision.entries.insert(nref, entry);
+                        }
+                    }
+                }
+                Ok(revision)
+            }
+            Token::Int(_) => self.chop_xref_stream(),
+            other => Err(self.unexpected("keyword `xref` or a cross-reference stream", other)),
+        }
+    }
+
+    /// Object `id`, parsed the first time it's asked for and cached after that.
+    ///
+    /// References to objects that don't exist (or to an older generation of one) resolve to
+    /// the null object, as per the spec.
+    pub(crate) fn resolve(&mut self, id: ObjectId) -> Result<&Object<'a>, ParseError> {
+        if !self.objects.contains_key(&id) {
+            let obj = self.load_obj(id)?;
+            self.objects.insert(id, obj);
+        }
+        Ok(&self.objects[&id])
+    }
+
+    fn load_obj(&mut self, id: ObjectId) -> Result<Object<'a>, ParseError> {
+        if !self.resolving.insert(id) {
+            return Err(ParseError::ReferenceCycle { id });
+        }
+
+        let saved = self.cur;
+        let result = match self.xref_table.get(&id.num) {
+            Some(&XrefEntry::InUse { offset, gen }) if gen == id.gen => {
+                let result = self.seek(offset).and_then(|()| self.chop_indirect_obj(id));
+                match result.map_err(|err| self.read_error(err)) {
+                    err @ Err(ParseError::Io { .. }) => err,
+                    // The offset may just be stale, so see if the object is somewhere else
+                    Err(err) => match self.scanned_objects().get(&id.num) {
+                        Some(&XrefEntry::InUse {
+                            offset: scanned,
+                            gen,
+                        }) if gen == id.gen && scanned != offset => {
+                            self.seek(scanned).and_then(|()| self.chop_indirect_obj(id))
+                        }
+                        _ => Err(err),
+                    },
+                    ok => ok,
+                }
+            }
+            Some(&XrefEntry::Compressed { stream, index }) if id.gen == 0 => {
+                self.chop_compressed_obj(id.num, stream, index)
+            }
+            _ => Ok(Object::Null),
+        };
+        self.cur = saved;
+
+        self.resolving.remove(&id);
+        result.map_err(|err| self.read_error(err))
+    }
+
+    /// `err`, or the I/O error behind it if part of the file couldn't be read.
+    fn read_error(&self, err: ParseError) -> ParseError {
+        match self.data.take_error() {
+            Some(err) => err.into(),
+            None => err,
+        }
+    }
+
+    /// Every object in the cross-reference table that's in use, sorted by object number.
+    pub(crate) fn object_ids(&self) -> Vec<ObjectId> {
+        let mut ids = self
+            .xref_table
+            .iter()
+            .filter_map(|(&num, entry)| match *entry {
+                XrefEntry::InUse { gen, .. } => Some(ObjectId { num, gen }),
+                XrefEntry::Compressed { .. } => Some(ObjectId { num, gen: 0 }),
+                XrefEntry::Free { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Parses the indirect object `num gen obj ... endobj` at the cursor.
+    fn chop_indirect_obj(&mut self, id: ObjectId) -> Result<Object<'a>, ParseError> {
+        self.skip_whitespace();
+        let begin = self.cur;
+        let found_num = self.chop_int_token("object number")?;
+        let found_gen = self.chop_int_token("generation number")?;
+        self.cur = begin;
+        if found_num != id.num as i64 || found_gen != id.gen as i64 {
+            return Err(self.invalid(format!(
+                "xref table points to object {}, but found object {} {}",
+                id, found_num, found_gen
+            )));
+        }
+        let obj = self.chop_obj()?;
+        if self.security.is_none() || self.encrypt_id == Some(id) {
+            return Ok(obj);
+        }
+        self.decrypt(id, obj)
+    }
+
+    /// Decrypts the strings and stream data of object `id`, which was just parsed from an
+    /// encrypted file, and decodes the stream data.
+    fn decrypt(&mut self, id: ObjectId, obj: Object<'a>) -> Result<Object<'a>, ParseError> {
+        let security = self.security.as_ref().unwrap();
+        match security
+            .decrypt_object(id, obj)
+            .map_err(ParseError::Crypt)?
+        {
+            Object::Stream { dict, data, .. } => {
+                let decoded = self.stream_contents(id, &dict, data.clone()).ok();
+                Ok(Object::Stream {
+                    dict,
+                    data,
+                    decoded,
+                })
+            }
+            obj => Ok(obj),
+        }
+    }
+
+    /// Deleted objects, as (object number, generation to use if the number is reused) pairs,
+    /// sorted by object number. Object 0, the head of the free list, is left out.
+    pub(crate) fn free_objects(&self) -> Vec<(usize, u16)> {
+        let mut free = self
+            .xref_table
+            .iter()
+            .filter_map(|(&nref, entry)| match *entry {
+                XrefEntry::Free { gen, .. } if nref != 0 => Some((nref, gen)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        free.sort_unstable();
+        free
+    }
+
+    /// The object number and generation a newly added object should use.
+    ///
+    /// Follows the free list starting at object 0, skipping numbers whose generation has hit
+    /// 65535 (which must never be reused), and otherwise allocates a number past the end.
+    pub(crate) fn next_free_object(&self) -> (usize, u16) {
+        let mut visited = HashSet::new();
+        let mut nref = 0;
+        while visited.insert(nref) {
+            let Some(&XrefEntry::Free { next, .. }) = self.xref_table.get(&nref) else {
+                break;
+            };
+            if next == 0 {
+                break;
+            }
+            match self.xref_table.get(&next) {
+                Some(&XrefEntry::Free { gen, .. }) if gen < 65535 => return (next, gen),
+                _ => nref = next,
+            }
+        }
+
+        let size = match self.trailer_dict.get("Size") {
+            Some(Object::Int(size)) => usize::try_from(*size).unwrap_or_default(),
+            _ => 0,
+        };
+        let past_last = self.xref_table.keys().max().map_or(0, |&last| last + 1);
+        (size.max(past_last), 0)
+    }
+
+    /// Parses object `nref`, stored as the `index`th object of the object stream `stream`.
+    fn chop_compressed_obj(
+        &mut self,
+        nref: usize,
+        stream: usize,
+        index: usize,
+    ) -> Result<Object<'a>, ParseError> {
+        self.load_object_stream(stream)?;
+        let stream_id = ObjectId {
+            num: stream,
+            gen: 0,
+        };
+        let Some(Object::Stream {
+            decoded: Some(data),
+            ..
+        }) = self.objects.get(&stream_id)
+        else {
+            unreachable!("load_object_stream() makes sure the stream is decoded");
+        };
+
+        let header = &self.object_streams[&stream];
+        let Some(&(found, offset)) = header.get(index) else {
+            return Err(self.invalid(format!(
+                "object stream {} has no object at index {}",
+                stream, index
+            )));
+        };
+        if found != nref {
+            return Err(self.invalid(format!(
+                "object stream {} has object {} at index {}, expected {}",
+                stream, found, index, nref
+            )));
+        }
+        // Don't let the object run into the next one, e.g. `1 2 R` being read as a reference
+        let end = header
+            .iter()
+            .map(|&(_, other)| other)
+            .filter(|&other| other > offset)
+            .min()
+            .unwrap_or(data.len());
+
+        match Parser::over(&data[offset..end]).chop_obj()? {
+            Object::Stream { .. } => {
+                Err(self.invalid(format!("object stream {} contains a stream", stream)))
+            }
+            obj => Ok(obj.into_owned()),
+        }
+    }
+
+    /// Makes sure the object stream `stream` is loaded and decoded, and its header parsed.
+    fn load_object_stream(&mut self, stream: usize) -> Result<(), ParseError> {
+        if self.object_streams.contains_key(&stream) {
+            return Ok(());
+        }
+
+        let id = ObjectId {
+            num: stream,
+            gen: 0,
+        };
+        let (dict, data, decoded) = match self.resolve(id)? {
+            Object::Stream {
+                dict,
+                data,
+                decoded,
+            } if dict.get("Type").and_then(Object::as_name) == Some("ObjStm") => {
+                (dict.clone(), data.clone(), decoded.is_some())
+            }
+            _ => {
+                return Err(self.invalid(format!("object {} isn't an object stream", stream)));
+            }
+        };
+        if !decoded {
+            // Decoding failed when the stream was parsed, so do it again to find out why
+            let decoded = self.stream_contents(id, &dict, data)?.into_owned();
+            if let Some(Object::Stream { decoded: slot, .. }) = self.objects.get_mut(&id) {
+                *slot = Some(Cow::Owned(decoded));
+            }
+        }
+
+        let Some(Object::Stream {
+            decoded: Some(data),
+            ..
+        }) = self.objects.get(&id)
+        else {
+            unreachable!("object stream was decoded above");
+        };
+        let header = Self::chop_objstm_header(&dict, data)?;
+        self.object_streams.insert(stream, header);
+        Ok(())
+    }
+
+    /// Parses the `/N` pairs of object numbers and offsets at the start of an object stream.
+    fn chop_objstm_header(dict: &Dict, data: &[u8]) -> Result<Vec<(usize, usize)>, ParseError> {
+        let mut parser = Parser::over(data);
+        let (Some(n), Some(first)) = (
+            dict.get("N").and_then(Object::as_int),
+            dict.get("First").and_then(Object::as_int),
+        ) else {
+            return Err(parser.invalid("object stream must have integer `N` and `First` entries"));
+        };
+        let first = match usize::try_from(first) {
+            Ok(first) if first <= data.len() => first,
+            _ => return Err(parser.invalid("`First` in object stream is out of range")),
+        };
+
+        parser.data = Source::Borrowed(&data[..first]);
+        let mut header = Vec::new();
+        for _ in 0..n {
+            let nref = parser.chop_int_token("object number in object stream header")?;
+            let offset = parser.chop_int_token("object offset in object stream header")?;
+            match (usize::try_from(nref), usize::try_from(offset)) {
+                (Ok(nref), Ok(offset)) if first + offset <= data.len() => {
+                    header.push((nref, first + offset))
+                }
+                _ => return Err(parser.invalid("object stream header entry is out of range")),
+            }
+        }
+
+        Ok(header)
+    }
+
+    /// The decrypted and decoded contents of stream `id`, with dictionary `dict` and raw
+    /// bytes `data`.
+    pub(crate) fn stream_contents(
+        &mut self,
+        id: ObjectId,
+        dict: &Dict<'a>,
+        data: Cow<'a, [u8]>,
+    ) -> Result<Cow<'a, [u8]>, ParseError> {
+        let data = match &self.security {
+            Some(security) => security
+                .decrypt_stream(id, dict, data)
+                .map_err(ParseError::Crypt)?,
+            None => data,
+        };
+        let offset = match self.xref_table.get(&id.num) {
+            Some(&XrefEntry::InUse { offset, gen }) if gen == id.gen => offset,
+            _ => self.cur,
+        };
+        self.decode_stream(dict, data, offset)
+    }
+
+    /// The decoded contents of a stream with dictionary `dict` and (decrypted) data `data`.
+    /// Filter errors are reported at `offset`, where the stream is in the file.
+    fn decode_stream(
+        &mut self,
+        dict: &Dict<'a>,
+        mut data: Cow<'a, [u8]>,
+        offset: usize,
+    ) -> Result<Cow<'a, [u8]>, ParseError> {
+        let filter = match dict.get("Filter") {
+            Some(filter) => self.direct(filter)?,
+            None => return Ok(data),
+        };
+        let parms = match dict.get("DecodeParms") {
+            Some(parms) => self.direct(parms)?,
+            None => Object::Null,
+        };
+
+        // A single filter can be given on its own, and its parameters then aren't in an array
+        let (filters, parms) = match (filter, parms) {
+            (Object::Array(filters), Object::Array(parms)) => (filters, parms),
+            (Object::Array(filters), parms) => (filters, vec![parms]),
+            (filter @ Object::Name(_), parms) => (vec![filter], vec![parms]),
+            _ => return Err(self.invalid("`Filter` must be a name or an array of names")),
+        };
+
+        // Filters are listed in the order they're to be applied when decoding
+        for (i, filter) in filters.iter().enumerate() {
+            let filter = self.direct(filter)?;
+            let Some(name) = filter.as_name() else {
+                return Err(self.invalid("`Filter` must contain only names"));
+            };
+            let parms = match parms.get(i) {
+                Some(parms) => self.direct(parms)?,
+                None => Object::Null,
+            };
+            // Parameters can be indirect too, like the `/JBIG2Globals` stream
+            let parms = match parms {
+                Object::Dict(parms) => Object::Dict(
+                    parms
+                        .into_iter()
+                        .map(|(key, value)| Ok((key, self.direct(&value)?)))
+                        .collect::<Result<_, ParseError>>()?,
+                ),
+                parms => parms,
+            };
+            data = filter::decode(name, &data, parms.as_dict(), self.max_decoded_len)
+                .map(Cow::Owned)
+                .map_err(|error| ParseError::Filter { offset, error })?;
+        }
+
+        Ok(data)
+    }
+
+    /// `obj`, or the object it refers to if it's a reference.
+    fn direct(&mut self, obj: &Object<'a>) -> Result<Object<'a>, ParseError> {
+        match obj {
+            Object::Reference(id) => Ok(self.resolve(*id)?.clone()),
+            _ => Ok(obj.clone()),
+        }
+    }
+
+    /// Parses a classic `xref` table and the `trailer` dictionary following it.
+    fn chop_xref_section(&mut self) -> Result<Revision<'a>, ParseError> {
+        let xref_offset = self.cur;
+        self.expect_token(Token::Keyword(Keyword::Xref), "keyword `xref`")?;
+
+        let mut entries = HashMap::new();
+        // Any number of subsections, each starting with a `first count` header
+        while self.peek_token()? != Token::Keyword(Keyword::Trailer) {
+            let start = self.chop_int_token("first object number of xref subsection")?;
+            let n_entries = self.chop_int_token("entry count of xref subsection")?;
+            if start < 0 || n_entries < 0 {
+                return Err(self.invalid("xref subsection header must be non-negative"));
+            }
+
+            for i in 0..n_entries {
+                let Some(nref) = start.checked_add(i).and_then(|n| usize::try_from(n).ok()) else {
+                    return Err(self.invalid("xref subsection goes past the largest object number"));
+                };
+                let offset = self.chop_ranged_int_token("byte offset of xref entry")?;
+                let ngen = self.chop_ranged_int_token("generation number of xref entry")?;
+
+                let entry_offset = self.cur;
+                match self.chop_token()? {
+                    Token::Keyword(Keyword::EntryInUse) => {
+                        entries.insert(nref, XrefEntry::InUse { offset, gen: ngen });
+                    }
+
+                    Token::Keyword(Keyword::EntryFree) => {
+                        entries.insert(
+                            nref,
+                            XrefEntry::Free {
+                                next: offset,
+                                gen: ngen,
+                            },
+                        );
+                    }
+
+                    other => {
+                        self.cur = entry_offset;
+                        return Err(self.unexpected("either `n` or `f`", other));
+                    }
+                }
+            }
+        }
+
+        self.expect_token(Token::Keyword(Keyword::Trailer), "keyword `trailer`")?;
+        let trailer = self.chop_dict_obj()?;
+
+        Ok(Revision {
+            xref_offset,
+            trailer,
+            entries,
+        })
+    }
+
+    /// Parses a cross-reference stream (PDF 1.5+), whose dictionary doubles as the trailer.
+    fn chop_xref_stream(&mut self) -> Result<Revision<'a>, ParseError> {
+        let begin = self.cur;
+        let (dict, data, decoded) = match self.chop_obj()? {
+            Object::Stream {
+                dict,
+                data,
+                decoded,
+            } => (dict, data, decoded),
+            other => {
+                self.cur = begin;
+                return Err(self.unexpected("a cross-reference stream", other));
+            }
+        };
+        self.cur = begin;
+
+        if dict.get("Type").and_then(Object::as_name) != Some("XRef") {
+            return Err(self.invalid("cross-reference stream must have `/Type /XRef`"));
+        }
+        let data = match decoded {
+            Some(decoded) => decoded,
+            None => self.decode_stream(&dict, data, begin)?,
+        };
+
+        let size = match dict.get("Size") {
+            Some(Object::Int(size)) if *size >= 0 => *size,
+            _ => return Err(self.invalid("`Size` in cross-reference stream must be an integer")),
+        };
+
+        let widths = match dict.get("W") {
+            Some(Object::Array(arr)) if arr.len() == 3 => arr
+                .iter()
+                .map(|w| match w {
+                    Object::Int(w) if (0..=8).contains(w) => Some(*w as usize),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        let Some(widths) = widths else {
+            return Err(self.invalid("`W` in cross-reference stream must be 3 integers up to 8"));
+        };
+
+        // Pairs of (first object number, entry count); defaults to `[0 Size]`
+        let subsections = match dict.get("Index") {
+            None => vec![(0, size)],
+            Some(Object::Array(arr)) if arr.len() % 2 == 0 => {
+                let ints = arr
+                    .iter()
+                    .map(|i| match i {
+                        Object::Int(i) if *i >= 0 => Some(*i),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>();
+                let Some(ints) = ints else {
+                    return Err(self.invalid("`Index` must contain non-negative integers"));
+                };
+                ints.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+            }
+            Some(_) => {
+                return Err(self.invalid("`Index` must be an array of integer pairs"));
+            }
+        };
+
+        let entry_width: usize = widths.iter().sum();
+        let mut rows = data.chunks_exact(entry_width.max(1));
+        let mut entries = HashMap::new();
+        for (start, count) in subsections {
+            let Some(end) = start.checked_add(count) else {
+                return Err(self.invalid("`Index` goes past the largest object number"));
+            };
+            for nref in start..end {
+                let Some(row) = rows.next() else {
+                    return Err(ParseError::UnexpectedEof {
+                        offset: begin,
+                        expected: "more entries in cross-reference stream",
+                    });
+                };
+
+                let mut fields = [0usize; 3];
+                let mut pos = 0;
+                for (field, &width) in fields.iter_mut().zip(&widths) {
+                    *field = row[pos..pos + width]
+                        .iter()
+                        .fold(0, |acc, &byte| (acc << 8) | byte as usize);
+                    pos += width;
+                }
+                // A missing type field means every entry is in use
+                let kind = if widths[0] == 0 { 1 } else { fields[0] };
+
+                let nref = nref as usize;
+                let [_, field2, field3] = fields;
+                let entry = match (kind, u16::try_from(field3)) {
+                    (0, Ok(gen)) => XrefEntry::Free { next: field2, gen },
+                    (1, Ok(gen)) => XrefEntry::InUse {
+                        offset: field2,
+                        gen,
+                    },
+                    (0 | 1, Err(_)) => {
+                        return Err(self.invalid(format!(
+                            "generation number {} of object {} is out of range",
+                            field3, nref
+                        )));
+                    }
+                    (2, _) => XrefEntry::Compressed {
+                        stream: field2,
+                        index: field3,
+                    },
+                    // Unknown types are to be treated as references to the null object
+                    _ => continue,
+                };
+                entries.insert(nref, entry);
+            }
+        }
+
+        Ok(Revision {
+            xref_offset: begin,
+            trailer: dict,
+            entries,
+        })
+    }
+
+    fn seek(&mut self, offset: usize) -> Result<(), ParseError> {
+        if offset >= self.data.len() {
+            return Err(self.invalid(format!("offset {} is past the end of the file", offset)));
+        }
+        self.cur = offset;
+        Ok(())
+    }
+
+    fn chop_char(&mut self) -> Option<u8> {
+        let ch = self.data.get(self.cur)?;
+        self.cur += 1;
+        Some(match ch {
+            b'\r' => {
+                if let Some(b'\n') = self.data.get(self.cur) {
+                    self.cur += 1;
+                }
+                b'\n'
+            }
+            _ => ch,
+        })
+    }
+
+    fn chop_char_backwards(&mut self) -> Option<u8> {
+        let ch = self.data.get(self.cur)?;
+        self.cur = self.cur.checked_sub(1)?;
+        Some(match ch {
+            b'\r' => b'\n',
+            b'\n' => {
+                if let (Some(b'\r'), Some(prev)) =
+                    (self.data.get(self.cur), self.cur.checked_sub(1))
+                {
+                    self.cur = prev;
+                }
+                b'\n'
+            }
+            _ => ch,
+        })
+    }
+
+    fn chop_n_chars(&mut self, n: usize) -> Result<Range<usize>, ParseError> {
+        let begin = self.cur;
+        for _ in 0..n {
+            if self.chop_char().is_none() {
+                return Err(self.eof("more characters"));
+            }
+        }
+        Ok(begin..self.cur)
+    }
+
+    fn slurp_n_bytes(&mut self, n: usize) -> Result<Range<usize>, ParseError> {
+        let begin = self.cur;
+        match begin.checked_add(n) {
+            Some(end) if end <= self.data.len() => {
+                self.cur = end;
+                Ok(begin..end)
+            }
+            _ => Err(ParseError::UnexpectedEof {
+                offset: self.data.len(),
+                expected: "stream data",
+            }),
+        }
+    }
+
+    fn chop_while(&mut self, predicate: fn(u8) -> bool) -> Range<usize> {
+        let begin = self.cur;
+        while self.data.get(self.cur).is_some_and(predicate) {
+            self.chop_char();
+        }
+        begin..self.cur
+    }
+
+    fn chop_word(&mut self) -> Range<usize> {
+        self.chop_while(Self::is_ascii_normal)
+    }
+
+    fn chop_int<T: FromStr>(&mut self, expected: &'static str) -> Result<T, ParseError> {
+        let begin = self.cur;
+        while self
+            .data
+            .get(self.cur)
+            .is_some_and(|ch| ch.is_ascii_digit())
+        {
+            self.cur += 1;
+        }
+        // Only ASCII digits were consumed, so this is always valid UTF-8.
+        let digits = self.data.bytes(begin..self.cur);
+        let digits = str::from_utf8(&digits).unwrap_or_default();
+        match T::from_str(digits) {
+            Ok(value) => Ok(value),
+            Err(_) => {
+                self.cur = begin;
+                Err(self.unexpected(expected, digits))
+            }
+        }
+    }
+
+    fn chop_int_token(&mut self, expected: &'static str) -> Result<i64, ParseError> {
+        self.skip_whitespace();
+        let begin = self.cur;
+        match self.chop_token()? {
+            Token::Int(i) => Ok(i),
+            other => {
+                self.cur = begin;
+                Err(self.unexpected(expected, other))
+            }
+        }
+    }
+
+    /// Like `chop_int_token`, for an integer that must fit in `T`, such as an offset or a
+    /// generation number.
+    fn chop_ranged_int_token<T: TryFrom<i64>>(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<T, ParseError> {
+        let value = self.chop_int_token(expected)?;
+        T::try_from(value)
+            .map_err(|_| self.invalid(format!("{} {} is out of range", expected, value)))
+    }
+
+    fn expect_char(&mut self, expected: u8, what: &'static str) -> Result<(), ParseError> {
+        let begin = self.cur;
+        match self.chop_char() {
+            Some(ch) if ch == expected => Ok(()),
+            Some(ch) => {
+                self.cur = begin;
+                Err(self.unexpected(what, ch as char))
+            }
+            None => Err(self.eof(what)),
+        }
+    }
+
+    fn expect_token(&mut self, expected: Token, what: &'static str) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        let begin = self.cur;
+        let token = self.chop_token()?;
+        if token != expected {
+            self.cur = begin;
+            return Err(self.unexpected(what, token));
+        }
+        Ok(())
+    }
+
+    /// Skips whitespace, and comments, which count as whitespace.
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.chop_while(Self::is_ascii_whitespace);
+            if self.data.get(self.cur) != Some(b'%') {
+                return;
+            }
+            let offset = self.cur;
+            self.cur += 1;
+            let text = self.chop_while(|ch| ch != b'\r' && ch != b'\n');
+            if self.keep_comments {
+                self.comments.insert(offset, self.data.slice(text));
+            }
+        }
+    }
+
+    fn chop_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_whitespace();
+        let Some(ch) = self.data.get(self.cur) else {
+            return Err(self.eof("a token"));
+        };
+        match ch {
+            b'<' => {
+                self.chop_char();
+                if let Some(b'<') = self.data.get(self.cur) {
+                    self.chop_char();
+                    return Ok(Token::DictBegin);
+                }
+
+                // Hex string
+
+                let mut result = Vec::<u8>::new();
+                // TODO: This doesn't need to be heap allocated but I can't be bothered to
+                // figure out the Rust way of doing this right now
+                let mut current_byte_hex = String::with_capacity(2);
+                loop {
+                    self.chop_while(Self::is_ascii_whitespace);
+                    let Some(digit) = self.data.get(self.cur) else {
+                        return Err(self.eof("`>` at end of hex string"));
+                    };
+                    if digit == b'>' {
+                        self.chop_char();
+                        if !current_byte_hex.is_empty() {
+                            current_byte_hex.push('0');
+                            result.push(
+                                u8::from_str_radix(&current_byte_hex, 16)
+                                    .expect("Only hexdigits appended to current_byte_string"),
+                            );
+                            current_byte_hex.clear();
+                        }
+                        return Ok(Token::String(result));
+                    }
+
+                    if !digit.is_ascii_hexdigit() {
+                        return Err(self.unexpected("hex digit in hex string", digit as char));
+                    }
+                    current_byte_hex.push(digit as char);
+                    self.chop_char();
+
+                    if current_byte_hex.len() == 2 {
+                        result.push(
+                            u8::from_str_radix(&current_byte_hex, 16)
+                                .expect("Only hexdigits appended to current_byte_string"),
+                        );
+                        current_byte_hex.clear();
+                    }
+                }
+            }
+
+            b'>' => {
+                if let Some(b'>') = self.data.get(self.cur + 1) {
+                    self.chop_n_chars(2)?;
+                    return Ok(Token::DictEnd);
+                }
+                Err(self.unexpected("a token", "stray `>`"))
+            }
+
+            b'[' => {
+                self.chop_char();
+                Ok(Token::ArrayBegin)
+            }
+
+            b']' => {
+                self.chop_char();
+                Ok(Token::ArrayEnd)
+            }
+
+            b'/' => {
+                self.chop_char();
+                Ok(Token::Solidus)
+            }
+
+            b'(' => {
+                self.chop_char();
+                let mut result = vec![];
+                // level of parens
+                // incremented for a left paren, decremented for a right paren
+                let mut level = 1;
+
+                while level > 0 {
+                    let Some(ch) = self.data.get(self.cur) else {
+                        return Err(self.eof("`)` at end of string"));
+                    };
+                    match ch {
+                        b'(' => {
+                            level += 1;
+                            self.chop_char();
+                            result.push(ch);
+                        }
+
+                        b')' => {
+                            level -= 1;
+                            self.chop_char();
+                            if level != 0 {
+                                result.push(ch);
+                            }
+                        }
+
+                        b'\\' => {
+                            self.chop_char();
+                            let Some(escaped) = self.data.get(self.cur) else {
+                                return Err(self.eof("escape character in string"));
+                            };
+                            match escaped {
+                                b'n' => {
+                                    self.chop_char();
+                                    result.push(b'\n');
+                                }
+                                b'r' => {
+                                    self.chop_char();
+                                    result.push(b'\r');
+                                }
+                                b't' => {
+                                    self.chop_char();
+                                    result.push(b'\t');
+                                }
+                                b'b' => {
+                                    self.chop_char();
+                                    result.push(0x08_u8);
+                                }
+                                b'f' => {
+                                    self.chop_char();
+                                    result.push(0x0C_u8);
+                                }
+                                b'(' => {
+                                    self.chop_char();
+                                    result.push(b'(');
+                                }
+                                b')' => {
+                                    self.chop_char();
+                                    result.push(b')');
+                                }
+                                b'\\' => {
+                                    self.chop_char();
+                                    result.push(b'\\');
+                                }
+
+                                b'\n' | b'\r' => {
+                                    self.chop_char();
+                                }
+
+                                b'0'..=b'7' => {
+                                    let mut value = 0u32;
+                                    let mut i = 0;
+                                    while i < 3
+                                        && matches!(self.data.get(self.cur), Some(b'0'..=b'7'))
+                                    {
+                                        let digit = self.data.get(self.cur).unwrap_or(b'0');
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.cur += 1;
+                                        i += 1;
+                                    }
+                                    // High-order overflow is ignored, as per the spec
+                                    result.push(value as u8);
+                                }
+
+                                _ => {
+                                    return Err(
+                                        self.unexpected("valid escape sequence", escaped as char)
+                                    );
+                                }
+                            }
+                        }
+
+                        _ => {
+                            if let Some(ch) = self.chop_char() {
+                                result.push(ch);
+                            }
+                        }
+                    }
+                }
+
+                Ok(Token::String(result))
+            }
+
+            b'0'..=b'9' | b'.' | b'+' | b'-' => {
+                let begin = self.cur;
+                let s = self.chop_while(|ch| matches!(ch, b'0'..=b'9' | b'.' | b'+' | b'-'));
+                // Only ASCII characters were consumed, so this is always valid UTF-8.
+                let s = self.data.bytes(s);
+                let s = str::from_utf8(&s).unwrap_or_default();
+                if let Ok(i) = s.parse::<i64>() {
+                    return Ok(Token::Int(i));
+                }
+                match s.parse::<f64>() {
+                    Ok(f) => Ok(Token::Float(f)),
+                    Err(_) => {
+                        self.cur = begin;
+                        Err(self.unexpected("a numeric literal", s))
+                    }
+                }
+            }
+
+            _ => {
+                let begin = self.cur;
+                let word = self.chop_word();
+                let word = self.data.bytes(word);
+
+                match str::from_utf8(&word)
+                    .ok()
+                    .and_then(|w| w.parse::<Keyword>().ok())
+                {
+                    Some(kw) => Ok(Token::Keyword(kw)),
+                    None => {
+                        self.cur = begin;
+                        let found = if word.is_empty() {
+                            format!("{:?}", ch as char)
+                        } else {
+                            format!("`{}`", String::from_utf8_lossy(&word))
+                        };
+                        Err(self.unexpected("a token", found))
+                    }
+                }
+            }
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Token, ParseError> {
+        let saved = self.cur;
+        let result = self.chop_token();
+        self.cur = saved;
+        result
+    }
+
+    fn chop_array_obj(&mut self) -> Result<Object<'a>, ParseError> {
+        self.expect_token(Token::ArrayBegin, "an array")?;
+
+        let mut result = Vec::new();
+
+        loop {
+            if self.peek_token()? == Token::ArrayEnd {
+                self.chop_token()?;
+                return Ok(Object::Array(result));
+            }
+
+            let obj = self.chop_obj()?;
+            result.push(obj);
+        }
+    }
+
+    fn chop_dict_obj(&mut self) -> Result<Dict<'a>, ParseError> {
+        self.expect_token(Token::DictBegin, "a dictionary")?;
+
+        let mut result = Dict::default();
+
+        loop {
+            if self.peek_token()? == Token::DictEnd {
+                self.chop_token()?;
+                return Ok(result);
+            }
+
+            self.skip_whitespace();
+            let key_offset = self.cur;
+            let key = match self.chop_obj()? {
+                Object::Name(key) => key,
+                other => {
+                    self.cur = key_offset;
+                    return Err(self.unexpected("name object as key in dictionary", other));
+                }
+            };
+
+            let value = self.chop_obj()?;
+
+            result.insert(key, value);
+        }
+    }
+
+    fn chop_name_obj(&mut self) -> Result<Object<'a>, ParseError> {
+        self.expect_token(Token::Solidus, "a name object")?;
+
+        let begin = self.cur;
+        let raw = self.chop_while(Self::is_ascii_normal);
+        if !self.data.bytes(raw.clone()).contains(&b'#') {
+            return Ok(Object::Name(Name::new(self.data.slice(raw))));
+        }
+        let raw = self.data.bytes(raw);
+
+        let mut name = Vec::with_capacity(raw.len());
+        let mut i = 0;
+        while i < raw.len() {
+            let hex = raw
+                .get(i + 1..i + 3)
+                .and_then(|hex| str::from_utf8(hex).ok());
+            match (raw[i], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+                (b'#', Some(0)) => {
+                    self.cur = begin;
+                    return Err(self.invalid("name objects can't contain NUL (`#00`)"));
+                }
+                (b'#', Some(byte)) => {
+                    name.push(byte);
+                    i += 3;
+                }
+                // Before PDF 1.2 `#` wasn't an escape, so a lone one is kept as it is
+                (byte, _) => {
+                    name.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        Ok(Object::Name(Name::new(name)))
+    }
+
+    fn chop_stream_obj(&mut self, dict: Dict<'a>) -> Result<Object<'a>, ParseError> {
+        self.expect_token(Token::Keyword(Keyword::Stream), "a stream")?;
+        // The keyword must be followed by CRLF or LF. A lone CR isn't an end of line there, so
+        // it's taken as the first byte of the data, unless `Length` doesn't fit that way
+        let lone_cr =
+            self.data.get(self.cur) == Some(b'\r') && self.data.get(self.cur + 1) != Some(b'\n');
+        if !lone_cr {
+            self.expect_char(b'\n', "end of line after `stream`")?;
+        }
+        let begin = self.cur;
+
+        let data = match self.stream_length(&dict).and_then(|length| {
+            let data = self.slurp_n_bytes(length)?;
+            self.expect_token(Token::Keyword(Keyword::EndStream), "`endstream`")?;
+            Ok(data)
+        }) {
+            Ok(data) => data,
+            Err(err) => {
+                // Damaged files often have a wrong `Length`, so look for `endstream` instead.
+                // A lone CR was then most likely meant as the end of the line after all.
+                let begin = begin + lone_cr as usize;
+                self.cur = begin;
+                if self.find_forwards(b"endstream", "`endstream`").is_err() {
+                    return Err(err);
+                }
+                let mut end = self.cur;
+                if end > begin && self.data.get(end - 1) == Some(b'\n') {
+                    end -= 1;
+                }
+                if end > begin && self.data.get(end - 1) == Some(b'\r') {
+                    end -= 1;
+                }
+                self.cur += b"endstream".len();
+                begin..end
+            }
+        };
+        let data = self.data.slice(data);
+
+        // Encrypted streams are decoded once they've been decrypted, which needs their id
+        let decoded = match self.security {
+            Some(_) => None,
+            None => self.decode_stream(&dict, data.clone(), begin).ok(),
+        };
+        Ok(Object::Stream {
+            dict,
+            data,
+            decoded,
+        })
+    }
+
+    fn stream_length(&mut self, dict: &Dict<'a>) -> Result<usize, ParseError> {
+        let length = match dict.get("Length") {
+            Some(Object::Int(i)) => *i,
+
+            Some(Object::Reference(id)) => {
+                let id = *id;
+                match self.resolve(id)? {
+                    Object::Int(i) => *i,
+                    Object::Null => {
+                        return Err(
+                            self.invalid(format!("`Length` refers to unknown object {}", id))
+                        );
+                    }
+                    _ => {
+                        return Err(
+                            self.invalid("`Length` in stream dictionary must be an integer")
+                        );
+                    }
+                }
+            }
+
+            Some(_) => {
+                return Err(self.invalid("`Length` in stream dictionary must be an integer"));
+            }
+
+            None => {
+                return Err(self.invalid("stream dictionary must have a `Length` field"));
+            }
+        };
+        usize::try_from(length)
+            .map_err(|_| self.invalid(format!("negative stream `Length` {}", length)))
+    }
+
+    fn chop_obj(&mut self) -> Result<Object<'a>, ParseError> {
+        self.skip_whitespace();
+        let begin = self.cur;
+        match self.peek_token()? {
+            Token::ArrayBegin => self.chop_array_obj(),
+            Token::DictBegin => Ok(Object::Dict(self.chop_dict_obj()?)),
+            Token::Solidus => self.chop_name_obj(),
+
+            Token::Int(i) => {
+                self.chop_token()?;
+                let saved = self.cur;
+                if let Ok(Token::Int(gennum)) = self.peek_token() {
+                    self.chop_token()?;
+                    match self.peek_token() {
+                        Ok(Token::Keyword(Keyword::R)) => {
+                            self.chop_token()?;
+                            return match (usize::try_from(i), u16::try_from(gennum)) {
+                                (Ok(num), Ok(gen)) => Ok(Object::Reference(ObjectId { num, gen })),
+                                _ => {
+                                    self.cur = begin;
+                                    Err(self.invalid(format!(
+                                        "invalid object reference `{} {} R`",
+                                        i, gennum
+                                    )))
+                                }
+                            };
+                        }
+
+                        Ok(Token::Keyword(Keyword::Obj)) => {
+                            self.chop_token()?;
+                            let ret = self.chop_obj()?;
+                            return match self.peek_token()? {
+                                Token::Keyword(Keyword::EndObj) => {
+                                    self.chop_token()?;
+                                    Ok(ret)
+                                }
+
+                                Token::Keyword(Keyword::Stream) => {
+                                    let Object::Dict(dict) = ret else {
+                                        return Err(self
+                                            .unexpected("dictionary object before `stream`", ret));
+                                    };
+                                    let streamobj = self.chop_stream_obj(dict)?;
+                                    self.expect_token(
+                                        Token::Keyword(Keyword::EndObj),
+                                        "`endobj` immediately after `endstream`",
+                                    )?;
+                                    Ok(streamobj)
+                                }
+
+                                other => Err(self.unexpected("`endobj`", other)),
+                            };
+                        }
+
+                        _ => {
+                            self.cur = saved;
+                        }
+                    }
+                }
+                Ok(Object::Int(i))
+            }
+
+            Token::Float(f) => {
+                self.chop_token()?;
+                Ok(Object::Float(f))
+            }
+
+            Token::String(str) => {
+                self.chop_token()?;
+                Ok(Object::String(str))
+            }
+
+            Token::Keyword(Keyword::True) => {
+                self.chop_token()?;
+                Ok(Object::Bool(true))
+            }
+
+            Token::Keyword(Keyword::False) => {
+                self.chop_token()?;
+                Ok(Object::Bool(false))
+            }
+
+            Token::Keyword(Keyword::Null) => {
+                self.chop_token()?;
+                Ok(Object::Null)
+            }
+
+            other => {
+                self.cur = begin;
+                Err(self.unexpected("an object", other))
+            }
+        }
+    }
+
+    /// Moves the cursor to the first occurrence of `target` at or after it.
+    fn find_forwards(&mut self, target: &[u8], expected: &'static str) -> Result<(), ParseError> {
+        match self.data.find(target, self.cur) {
+            Some(pos) => {
+                self.cur = pos;
+                Ok(())
+            }
+            None => Err(ParseError::UnexpectedEof {
+                offset: self.data.len(),
+                expected,
+            }),
+        }
+    }
+
+    /// Moves the cursor to the last occurrence of `target` starting at or before it.
+    fn find_backwards(&mut self, target: &[u8], expected: &'static str) -> Result<(), ParseError> {
+        match self.data.rfind(target, self.cur + target.len()) {
+            Some(pos) => {
+                self.cur = pos;
+                Ok(())
+            }
+            None => Err(ParseError::UnexpectedEof {
+                offset: 0,
+                expected,
+            }),
+        }
+    }
+
+    /// A short, printable excerpt of the input around `offset`, for error messages.
+    fn context(&self, offset: usize) -> String {
+        let begin = offset
+            .saturating_sub(Self::CONTEXT_RADIUS)
+            .min(self.data.len());
+        let end = offset
+            .saturating_add(Self::CONTEXT_RADIUS)
+            .min(self.data.len());
+        self.data.bytes(begin..end).escape_ascii().to_string()
+    }
+
+    fn unexpected(&self, expected: &'static str, found: impl fmt::Debug) -> ParseError {
+        ParseError::UnexpectedToken {
+            offset: self.cur,
+            expected,
+            found: format!("{:?}", found),
+            context: self.context(self.cur),
+        }
+    }
+
+    fn eof(&self, expected: &'static str) -> ParseError {
+        ParseError::UnexpectedEof {
+            offset: self.cur,
+            expected,
+        }
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> ParseError {
+        ParseError::InvalidValue {
+            offset: self.cur,
+            message: message.into(),
+            context: self.context(self.cur),
+        }
+    }
+
+    pub(crate) fn is_ascii_normal(x: u8) -> bool {
+        !Self::is_ascii_whitespace(x) && !Self::is_ascii_delim(x)
+    }
+
+    pub(crate) fn is_ascii_whitespace(x: u8) -> bool {
+        matches!(x, b'\0' | b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+    }
+
+    fn is_ascii_delim(x: u8) -> bool {
+        matches!(
+            x,
+            b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+        )
+    }
+}
+
+/// A file with `objects` numbered from 1, a classic xref table and a trailer with
+/// `trailer` in it besides `/Size`.
+#[cfg(test)]
+pub(crate) fn file(objects: &[&str], trailer: &str) -> Vec<u8> {
+    let mut file = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(file.len());
+        file.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).bytes());
+    }
+    let xref = file.len();
+    file.extend(format!("xref\n0 {}\n0000000000 65535 f\r\n", objects.len() + 1).bytes());
+    for offset in offsets {
+        file.extend(format!("{:010} 00000 n\r\n", offset).bytes());
+    }
+    file.extend(
+        format!(
+            "trailer\n<< /Size {} {} >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            trailer,
+            xref
+        )
+        .bytes(),
+    );
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stream object with `dict` entries besides `/Length`.
+    fn stream(dict: &str, data: &str) -> String {
+        format!(
+            "<< {} /Length {} >>\nstream\n{}\nendstream",
+            dict,
+            data.len(),
+            data
+        )
+    }
+
+    /// Appends `objects`, as (object number, contents) pairs, to `file`, and returns where
+    /// each one starts.
+    fn append(file: &mut Vec<u8>, objects: &[(usize, &str)]) -> Vec<usize> {
+        let mut offsets = Vec::new();
+        for (num, object) in objects {
+            offsets.push(file.len());
+            file.extend(format!("{} 0 obj\n{}\nendobj\n", num, object).bytes());
+        }
+        offsets
+    }
+
+    /// The entries of a cross-reference stream with `/W [1 2 1]`, hex encoded for
+    /// `/ASCIIHexDecode`.
+    fn xref_rows(rows: &[(u8, usize, u8)]) -> String {
+        let mut hex = String::new();
+        for (kind, field2, field3) in rows {
+            hex += &format!("{:02X}{:04X}{:02X}", kind, field2, field3);
+        }
+        hex + ">"
+    }
+
+    fn startxref(file: &mut Vec<u8>, xref: usize) {
+        file.extend(format!("startxref\n{}\n%%EOF\n", xref).bytes());
+    }
+
+    fn parse(file: &[u8]) -> Parser<'_> {
+        Parser::new(Source::from(file), &Options::default()).unwrap()
+    }
+
+    fn id(num: usize) -> ObjectId {
+        ObjectId { num, gen: 0 }
+    }
+
+    fn decoded<'p>(parser: &'p mut Parser, num: usize) -> &'p [u8] {
+        match parser.resolve(id(num)).unwrap() {
+            Object::Stream {
+                decoded: Some(decoded),
+                ..
+            } => decoded,
+            other => panic!("object {} isn't a decoded stream: {:?}", num, other),
+        }
+    }
+
+    #[test]
+    fn filter_pipeline() {
+        // Rows of `ABCD` with the PNG Up predictor, deflated and then hex encoded
+        let deflated = "78DA6372746262620200026B008C>";
+        let file = file(
+            &[
+                &stream(
+                    "/Filter [/ASCIIHexDecode /FlateDecode] \
+                     /DecodeParms [null << /Predictor 12 /Columns 2 >>]",
+                    deflated,
+                ),
+                &stream(
+                    "/Filter /ASCIIHexDecode /DecodeParms << /Predictor 12 /Columns 2 >>",
+                    "024142020202>",
+                ),
+                &stream("/Filter [/FlateDecode /ASCIIHexDecode]", deflated),
+            ],
+            "",
+        );
+        let mut parser = parse(&file);
+        assert_eq!(decoded(&mut parser, 1), b"ABCD");
+        // A lone filter's parameters aren't in an array, and are only for filters that take
+        // them
+        assert_eq!(decoded(&mut parser, 2), b"\x02AB\x02\x02\x02");
+        // Filters in the wrong order
+        assert!(matches!(
+            parser.resolve(id(3)).unwrap(),
+            Object::Stream { decoded: None, .. }
+        ));
+    }
+
+    #[test]
+    fn xref_stream() {
+        let mut file = b"%PDF-1.5\n".to_vec();
+        let offsets = append(&mut file, &[(1, "<< /Type /Catalog >>"), (2, "(two)")]);
+        let xref = file.len();
+        // Object 4 is free, and 5 has an unknown type; 3 isn't listed at all
+        let rows = xref_rows(&[
+            (0, 0, 255),
+            (1, offsets[0], 0),
+            (1, offsets[1], 0),
+            (0, 0, 1),
+            (7, 0, 0),
+        ]);
+        append(
+            &mut file,
+            &[(
+                3,
+                &stream(
+                    "/Type /XRef /Size 6 /Root 1 0 R /W [1 2 1] /Index [0 3 4 2] \
+                     /Filter /ASCIIHexDecode",
+                    &rows,
+                ),
+            )],
+        );
+        startxref(&mut file, xref);
+
+        let mut parser = parse(&file);
+        assert!(parser.recovered_from.is_none());
+        assert!(matches!(
+            parser.trailer_dict.get("Root"),
+            Some(&Object::Reference(root)) if root == id(1)
+        ));
+        assert_eq!(parser.object_ids(), [id(1), id(2)]);
+        assert_eq!(parser.free_objects(), [(4, 1)]);
+        assert!(matches!(parser.resolve(id(2)).unwrap(), Object::String(s) if s == b"two"));
+        assert!(matches!(parser.resolve(id(5)).unwrap(), Object::Null));
+    }
+
+    #[test]
+    fn hybrid_xref() {
+        let mut file = b"%PDF-1.4\n".to_vec();
+        let offsets = append(
+            &mut file,
+            &[(1, "<< /Type /Catalog >>"), (2, "(two)"), (3, "(three)")],
+        );
+        // Object 3 is hidden from readers that don't know about cross-reference streams, and
+        // the stream's entry for 2 mustn't override the table's
+        let stm = file.len();
+        let rows = xref_rows(&[(1, 0, 0), (1, offsets[2], 0)]);
+        append(
+            &mut file,
+            &[(
+                4,
+                &stream(
+                    "/Type /XRef /Size 5 /W [1 2 1] /Index [2 2] /Filter /ASCIIHexDecode",
+                    &rows,
+                ),
+            )],
+        );
+        let xref = file.len();
+        file.extend(b"xref\n0 4\n0000000000 65535 f\r\n");
+        file.extend(format!("{:010} 00000 n\r\n", offsets[0]).bytes());
+        file.extend(format!("{:010} 00000 n\r\n", offsets[1]).bytes());
+        file.extend(b"0000000000 00001 f\r\n");
+        file.extend(format!("trailer\n<< /Size 5 /Root 1 0 R /XRefStm {} >>\n", stm).bytes());
+        startxref(&mut file, xref);
+
+        let mut parser = parse(&file);
+        assert!(parser.recovered_from.is_none());
+        assert!(matches!(parser.resolve(id(2)).unwrap(), Object::String(s) if s == b"two"));
+        assert!(matches!(parser.resolve(id(3)).unwrap(), Object::String(s) if s == b"three"));
+    }
+
+    #[test]
+    fn xref_stream_generation_out_of_range() {
+        let mut file = b"%PDF-1.5\n".to_vec();
+        let offsets = append(&mut file, &[(1, "<< /Type /Catalog >>")]);
+        let xref = file.len();
+        let rows = format!("00000000FFFF01{:04X}010000>", offsets[0]);
+        append(
+            &mut file,
+            &[(
+                2,
+                &stream(
+                    "/Type /XRef /Size 2 /Root 1 0 R /W [1 2 3] /Filter /ASCIIHexDecode",
+                    &rows,
+                ),
+            )],
+        );
+        startxref(&mut file, xref);
+
+        // The file is still readable by scanning it for objects
+        let parser = parse(&file);
+        assert!(matches!(
+            &parser.recovered_from,
+            Some(ParseError::InvalidValue { message, .. })
+                if message == "generation number 65536 of object 1 is out of range"
+        ));
+        assert_eq!(parser.object_ids(), [id(1), id(2)]);
+    }
+
+    #[test]
+    fn object_stream() {
+        let mut file = b"%PDF-1.5\n".to_vec();
+        let objects = "3 0 4 2 5 9 1 (four) [3 0 R]";
+        let mut offsets = append(
+            &mut file,
+            &[
+                (1, "<< /Type /Catalog >>"),
+                (2, &stream("/Type /ObjStm /N 3 /First 12", objects)),
+            ],
+        );
+        offsets.push(file.len());
+        // Object 7 claims to be in the stream too, but isn't
+        let rows = xref_rows(&[
+            (0, 0, 255),
+            (1, offsets[0], 0),
+            (1, offsets[1], 0),
+            (2, 2, 0),
+            (2, 2, 1),
+            (2, 2, 2),
+            (1, offsets[2], 0),
+            (2, 2, 3),
+        ]);
+        append(
+            &mut file,
+            &[(
+                6,
+                &stream(
+                    "/Type /XRef /Size 8 /Root 1 0 R /W [1 2 1] /Filter /ASCIIHexDecode",
+                    &rows,
+                ),
+            )],
+        );
+        startxref(&mut file, offsets[2]);
+
+        let mut parser = parse(&file);
+        assert_eq!(parser.object_ids(), [1, 2, 3, 4, 5, 6, 7].map(id).to_vec());
+        assert!(matches!(parser.resolve(id(3)).unwrap(), Object::Int(1)));
+        assert!(matches!(parser.resolve(id(4)).unwrap(), Object::String(s) if s == b"four"));
+        assert!(matches!(
+            parser.resolve(id(5)).unwrap(),
+            Object::Array(items) if matches!(items[..], [Object::Reference(item)] if item == id(3))
+        ));
+        assert!(matches!(
+            parser.resolve(id(7)),
+            Err(ParseError::InvalidValue { message, .. })
+                if message == "object stream 2 has no object at index 3"
+        ));
+        // Compressed objects always have generation 0
+        assert!(matches!(
+            parser.resolve(ObjectId { num: 3, gen: 1 }).unwrap(),
+            Object::Null
+        ));
+    }
+
+    #[test]
+    fn incremental_update() {
+        let mut file = file(&["<< /Type /Catalog >>", "(old)", "(three)"], "/Root 1 0 R");
+        let prev = file.windows(4).position(|w| w == b"xref").unwrap();
+        // Changes object 2, deletes 3 and adds 4
+        let offsets = append(&mut file, &[(2, "(new)"), (4, "(four)")]);
+        let xref = file.len();
+        file.extend(b"xref\n0 1\n0000000003 65535 f\r\n2 3\n");
+        file.extend(format!("{:010} 00000 n\r\n", offsets[0]).bytes());
+        file.extend(b"0000000000 00001 f\r\n");
+        file.extend(format!("{:010} 00000 n\r\n", offsets[1]).bytes());
+        file.extend(format!("trailer\n<< /Size 5 /Root 1 0 R /Prev {} >>\n", prev).bytes());
+        startxref(&mut file, xref);
+
+        let mut parser = parse(&file);
+        assert!(parser.recovered_from.is_none());
+        assert_eq!(parser.revisions.len(), 2);
+        assert_eq!(parser.revisions[0].xref_offset, prev);
+        assert_eq!(parser.revisions[1].xref_offset, xref);
+        let mut changed = parser.revisions[1]
+            .entries
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        changed.sort_unstable();
+        assert_eq!(changed, [0, 2, 3, 4]);
+        assert!(matches!(
+            parser.trailer_dict.get("Size"),
+            Some(Object::Int(5))
+        ));
+
+        assert_eq!(parser.object_ids(), [id(1), id(2), id(4)]);
+        assert!(matches!(parser.resolve(id(2)).unwrap(), Object::String(s) if s == b"new"));
+        assert!(matches!(parser.resolve(id(3)).unwrap(), Object::Null));
+        assert!(matches!(parser.resolve(id(4)).unwrap(), Object::String(s) if s == b"four"));
+    }
+
+    #[test]
+    fn prev_loop() {
+        let mut file = b"%PDF-1.4\n".to_vec();
+        append(&mut file, &[(1, "<< /Type /Catalog >>")]);
+        let xref = file.len();
+        file.extend(b"xref\n0 2\n0000000000 65535 f\r\n0000000009 00000 n\r\n");
+        file.extend(format!("trailer\n<< /Size 2 /Root 1 0 R /Prev {} >>\n", xref).bytes());
+        startxref(&mut file, xref);
+
+        let mut parser = parse(&file);
+        assert!(matches!(
+            &parser.recovered_from,
+            Some(ParseError::InvalidValue { message, .. })
+                if message == "`Prev` chain of xref sections loops back on itself"
+        ));
+        assert!(matches!(parser.resolve(id(1)).unwrap(), Object::Dict(_)));
+    }
+
+    #[test]
+    fn free_list() {
+        let mut file = b"%PDF-1.4\n".to_vec();
+        let offsets = append(
+            &mut file,
+            &[(1, "<< /Type /Catalog >>"), (2, "(two)"), (5, "(five)")],
+        );
+        // The free list runs 0, 4, 3, and 4 can't be reused since its generation is used up
+        let xref = file.len();
+        file.extend(b"xref\n0 1\n0000000004 65535 f\r\n1 2\n");
+        file.extend(format!("{:010} 00000 n\r\n", offsets[0]).bytes());
+        file.extend(format!("{:010} 00000 n\r\n", offsets[1]).bytes());
+        file.extend(b"3 3\n0000000000 00002 f\r\n0000000003 65535 f\r\n");
+        file.extend(format!("{:010} 00000 n\r\n", offsets[2]).bytes());
+        file.extend(b"8 0\ntrailer\n<< /Size 6 /Root 1 0 R >>\n");
+        startxref(&mut file, xref);
+
+        let mut parser = parse(&file);
+        assert!(parser.recovered_from.is_none());
+        assert_eq!(parser.object_ids(), [id(1), id(2), id(5)]);
+        assert!(matches!(parser.resolve(id(5)).unwrap(), Object::String(s) if s == b"five"));
+        assert_eq!(parser.free_objects(), [(3, 2), (4, 65535)]);
+        assert_eq!(parser.next_free_object(), (3, 2));
+    }
+
+    #[test]
+    fn no_free_list() {
+        // New objects go past the end
+        let file = file(&["<< /Type /Catalog >>", "null"], "/Root 1 0 R");
+        let parser = parse(&file);
+        assert_eq!(parser.free_objects(), []);
+        assert_eq!(parser.next_free_object(), (3, 0));
+    }
+
+    #[test]
+    fn xref_subsection_out_of_range() {
+        let mut file = b"%PDF-1.4\n".to_vec();
+        append(&mut file, &[(1, "<< /Type /Catalog >>")]);
+        let xref = file.len();
+        file.extend(b"xref\n0 1\n0000000000 65535 f\r\n");
+        file.extend(b"9223372036854775807 2\n0000000009 00000 n\r\n0000000009 00000 n\r\n");
+        file.extend(b"trailer\n<< /Size 2 /Root 1 0 R >>\n");
+        startxref(&mut file, xref);
+
+        let mut parser = parse(&file);
+        assert!(matches!(
+            &parser.recovered_from,
+            Some(ParseError::InvalidValue { message, .. })
+                if message == "xref subsection goes past the largest object number"
+        ));
+        assert!(matches!(parser.resolve(id(1)).unwrap(), Object::Dict(_)));
+    }
+
+    #[test]
+    fn lazy_resolution() {
+        let file = file(
+            &[
+                "<< /Type /Catalog >>",
+                "<< /Length 3 0 R >>\nstream\nhello\nendstream",
+                "5",
+                "<< /Length 4 0 R >>\nstream\nloop\nendstream",
+                "<< /Broken",
+            ],
+            "/Root 1 0 R",
+        );
+        // Nothing is parsed up front, so a broken object doesn't stop the file being read
+        let mut parser = parse(&file);
+        assert!(parser.objects.is_empty());
+        assert!(parser.resolve(id(5)).is_err());
+
+        // `Length` can refer to an object further on in the file
+        assert_eq!(decoded(&mut parser, 2), b"hello");
+        assert!(parser.objects.contains_key(&id(3)));
+        // or to the stream itself, in which case it's found by looking for `endstream`
+        assert_eq!(decoded(&mut parser, 4), b"loop");
+        assert!(parser.resolving.is_empty());
+    }
+
+    #[test]
+    fn references() {
+        let file = file(
+            &[
+                "<< /Type /Catalog >>",
+                "[3 0 R 1 1 R 9 0 R]",
+                "4 0 R",
+                "(four)",
+                "6 0 R",
+                "5 0 R",
+            ],
+            "/Root 1 0 R",
+        );
+        let mut doc = crate::Document::from_bytes(&file).unwrap();
+        let Object::Array(items) = doc.get_object(id(2)).unwrap().clone() else {
+            panic!("object 2 isn't an array");
+        };
+        assert!(matches!(items[0], Object::Reference(item) if item == id(3)));
+
+        // Chains of references are followed to the end
+        assert!(matches!(doc.deref(&items[0]).unwrap(), Object::String(s) if s == b"four"));
+        // and references to an older generation or a missing object are null
+        assert!(matches!(doc.deref(&items[1]).unwrap(), Object::Null));
+        assert!(matches!(doc.deref(&items[2]).unwrap(), Object::Null));
+        // but not ones that go round in circles
+        assert!(matches!(
+            doc.deref(&Object::Reference(id(5))),
+            Err(ParseError::ReferenceCycle { .. })
+        ));
+        assert!(matches!(
+            doc.deref(&Object::Int(7)).unwrap(),
+            Object::Int(7)
+        ));
+    }
+
+    #[test]
+    fn recovery() {
+        // `startxref` points nowhere, and object 2 is there twice, the newer one last
+        let mut file = b"%PDF-1.4\n".to_vec();
+        append(
+            &mut file,
+            &[(1, "<< /Type /Catalog >>"), (2, "(old)"), (2, "(new)")],
+        );
+        file.extend(b"trailer\n<< /Size 3 /Root 1 0 R /Info 2 0 R >>\n");
+        startxref(&mut file, 9999);
+
+        let mut parser = parse(&file);
+        assert!(matches!(
+            &parser.recovered_from,
+            Some(ParseError::InvalidValue { message, .. })
+                if message == "offset 9999 is past the end of the file"
+        ));
+        assert!(parser.trailer_dict.contains_key("Info"));
+        assert_eq!(parser.object_ids(), [id(1), id(2)]);
+        assert!(matches!(parser.resolve(id(2)).unwrap(), Object::String(s) if s == b"new"));
+    }
+
+    #[test]
+    fn recovery_without_trailer() {
+        // Only a catalog, and an object stream the scan can't see into
+        let mut file = b"%PDF-1.5\n".to_vec();
+        append(
+            &mut file,
+            &[
+                (1, "<< /Type /Catalog /Pages 3 0 R >>"),
+                (
+                    2,
+                    &stream("/Type /ObjStm /N 1 /First 4", "3 0 << /Type /Pages >>"),
+                ),
+            ],
+        );
+        file.extend(b"%%EOF\n");
+
+        let mut parser = parse(&file);
+        assert!(parser.recovered_from.is_some());
+        assert!(matches!(
+            parser.trailer_dict.get("Root"),
+            Some(&Object::Reference(root)) if root == id(1)
+        ));
+        assert!(matches!(
+            parser.trailer_dict.get("Size"),
+            Some(Object::Int(4))
+        ));
+        assert!(matches!(parser.resolve(id(3)).unwrap(), Object::Dict(_)));
+
+        // With nothing to go on, there's no recovering
+        assert!(Parser::new(
+            Source::from(&b"%PDF-1.4\n1 0 obj\n5\nendobj\n"[..]),
+            &Options::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn stale_offset() {
+        // The xref table is fine except for object 2, which it puts at object 1
+        let mut file = file(&["<< /Type /Catalog >>", "(two)"], "/Root 1 0 R");
+        let xref = file.windows(4).position(|w| w == b"xref").unwrap();
+        let entry = xref + "xref\n0 3\n".len() + 2 * 20;
+        file[entry..entry + 10].copy_from_slice(b"0000000009");
+
+        let mut parser = parse(&file);
+        assert!(parser.recovered_from.is_none());
+        assert!(matches!(parser.resolve(id(2)).unwrap(), Object::String(s) if s == b"two"));
+    }
+
+    #[test]
+    fn name_round_trip() {
+        let names: [&[u8]; 8] = [
+            b"Type",
+            b"",
+            b"Lime Green",
+            b"#1 (of [2]) <x> {y} /z %w",
+            b"tab\tline\r\nfeed\x0c",
+            "\u{141}\u{f3}d\u{17a}".as_bytes(),
+            b"\x01\x7f\x80\xff",
+            b"a#zz",
+        ];
+        for bytes in names {
+            let name = Name::new(bytes);
+            let text = name.to_string();
+            assert!(
+                text[1..]
+                    .bytes()
+                    .all(|byte| Parser::is_ascii_normal(byte) && byte.is_ascii_graphic()),
+                "{:?}",
+                text
+            );
+            match Parser::over(text.as_bytes()).chop_obj().unwrap() {
+                Object::Name(parsed) => assert_eq!(parsed, name, "{:?}", text),
+                other => panic!("{:?} isn't a name: {:?}", text, other),
+            }
+        }
+        assert_eq!(Name::from("Lime Green#1").to_string(), "/Lime#20Green#231");
+        assert_eq!(Name::new("\u{e9}".as_bytes()).to_string(), "/#C3#A9");
+
+        // A `#` that isn't followed by two hex digits is taken as it is, but NUL isn't allowed
+        assert!(matches!(
+            Parser::over(&b"/a#zz#4"[..]).chop_obj().unwrap(),
+            Object::Name(name) if name.as_bytes() == b"a#zz#4"
+        ));
+        assert!(Parser::over(&b"/a#00"[..]).chop_obj().is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Where the bytes of a file come from.
+
+mod lazy;
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod mmap;
+
+use std::{borrow::Cow, io, ops::Range, sync::Arc};
+
+pub(crate) use lazy::LazyFile;
+#[cfg(all(unix, target_pointer_width = "64"))]
+pub(crate) use mmap::Mapping;
+
+/// The bytes of a file being parsed: borrowed from the caller, mapped into memory, or read
+/// in a chunk at a time as they're needed.
+#[derive(Clone, Debug)]
+pub(crate) enum Source<'a> {
+    Borrowed(&'a [u8]),
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    Mapped(Arc<Mapping>),
+    Lazy(Arc<LazyFile>),
+}
+
+impl<'a> Source<'a> {
+    /// How many bytes to look through at once when searching a file that's read lazily.
+    const SEARCH_BLOCK: usize = 64 * 1024;
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Source::Borrowed(data) => data.len(),
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Source::Mapped(mapping) => mapping.bytes().len(),
+            Source::Lazy(file) => file.len(),
+        }
+    }
+
+    /// The byte at `offset`, or `None` past the end of the file (or if it couldn't be read).
+    pub(crate) fn get(&self, offset: usize) -> Option<u8> {
+        match self {
+            Source::Borrowed(data) => data.get(offset).copied(),
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Source::Mapped(mapping) => mapping.bytes().get(offset).copied(),
+            Source::Lazy(file) => file.get(offset),
+        }
+    }
+
+    /// The bytes in `range`, to look at while parsing. Only a lazily read file copies them,
+    /// if they straddle two chunks.
+    pub(crate) fn bytes(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        match self {
+            Source::Borrowed(data) => Cow::Borrowed(&data[range]),
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Source::Mapped(mapping) => Cow::Borrowed(&mapping.bytes()[range]),
+            Source::Lazy(file) => file.bytes(range),
+        }
+    }
+
+    /// The bytes in `range`, to be kept in a parsed object. They're only borrowed if the
+    /// caller lent us the file, since anything else goes away with the parser.
+    pub(crate) fn slice(&self, range: Range<usize>) -> Cow<'a, [u8]> {
+        match self {
+            Source::Borrowed(data) => Cow::Borrowed(&data[range]),
+            _ => Cow::Owned(self.bytes(range).into_owned()),
+        }
+    }
+
+    /// The offset of the first occurrence of `target` starting at or after `from`.
+    pub(crate) fn find(&self, target: &[u8], from: usize) -> Option<usize> {
+        let mut begin = from;
+        while begin < self.len() {
+            // Overlap the blocks, so a match can straddle two of them
+            let end = (begin + Self::SEARCH_BLOCK + target.len()).min(self.len());
+            let block = self.bytes(begin..end);
+            if let Some(found) = block.windows(target.len()).position(|w| w == target) {
+                return Some(begin + found);
+            }
+            begin += Self::SEARCH_BLOCK;
+        }
+        None
+    }
+
+    /// The offset of the last occurrence of `target` that ends at or before `end`.
+    pub(crate) fn rfind(&self, target: &[u8], end: usize) -> Option<usize> {
+        let mut end = end.min(self.len());
+        while end >= target.len() {
+            let begin = end.saturating_sub(Self::SEARCH_BLOCK + target.len());
+            let block = self.bytes(begin..end);
+            if let Some(found) = block.windows(target.len()).rposition(|w| w == target) {
+                return Some(begin + found);
+            }
+            if begin == 0 {
+                break;
+            }
+            end = begin + target.len();
+        }
+        None
+    }
+
+    /// Why part of the file couldn't be read, if it couldn't since this was last called.
+    /// Parsing carries on as if the file ended there, so errors caused by a failed read
+    /// should be swapped for this.
+    pub(crate) fn take_error(&self) -> Option<io::Error> {
+        match self {
+            Source::Lazy(file) => file.take_error(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for Source<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        Source::Borrowed(data)
+    }
+}
@@ -0,0 +1,123 @@
+//! Files that are read a chunk at a time, as the parser gets to them, so only the parts of a
+//! large file that are needed end up in memory.
+
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+pub struct LazyFile {
+    reader: Mutex<Box<dyn ReadSeek>>,
+    len: usize,
+    /// Each chunk of the file, once it's been read.
+    chunks: Vec<OnceLock<Box<[u8]>>>,
+    /// The first error that a chunk couldn't be read because of, if one couldn't.
+    error: Mutex<Option<io::Error>>,
+}
+
+impl LazyFile {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    pub fn new(mut reader: impl ReadSeek + 'static) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let len = usize::try_from(len)
+            .map_err(|_| io::Error::new(io::ErrorKind::OutOfMemory, "file is too large"))?;
+        Ok(Self {
+            reader: Mutex::new(Box::new(reader)),
+            len,
+            chunks: (0..len.div_ceil(Self::CHUNK_SIZE))
+                .map(|_| OnceLock::new())
+                .collect(),
+            error: Mutex::new(None),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The `index`th chunk of the file, reading it in if it hasn't been yet. Chunks that
+    /// couldn't be read are tried again next time.
+    fn chunk(&self, index: usize) -> Option<&[u8]> {
+        let cell = self.chunks.get(index)?;
+        if let Some(chunk) = cell.get() {
+            return Some(chunk);
+        }
+
+        let begin = index * Self::CHUNK_SIZE;
+        let mut chunk = vec![0; Self::CHUNK_SIZE.min(self.len - begin)];
+        let mut reader = self.reader.lock().unwrap_or_else(PoisonError::into_inner);
+        let read = reader
+            .seek(SeekFrom::Start(begin as u64))
+            .and_then(|_| reader.read_exact(&mut chunk));
+        match read {
+            Ok(()) => Some(cell.get_or_init(|| chunk.into_boxed_slice())),
+            Err(err) => {
+                let mut error = self.error.lock().unwrap_or_else(PoisonError::into_inner);
+                error.get_or_insert(err);
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, offset: usize) -> Option<u8> {
+        let chunk = self.chunk(offset / Self::CHUNK_SIZE)?;
+        chunk.get(offset % Self::CHUNK_SIZE).copied()
+    }
+
+    /// The bytes in `range`, which are copied if they're in more than one chunk. They stop
+    /// short at the first chunk that can't be read.
+    pub fn bytes(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        if range.is_empty() {
+            return Cow::Borrowed(&[]);
+        }
+        let first = range.start / Self::CHUNK_SIZE;
+        let last = (range.end - 1) / Self::CHUNK_SIZE;
+        let within = |index: usize, chunk: &[u8]| {
+            let begin = index * Self::CHUNK_SIZE;
+            let from = range.start.saturating_sub(begin).min(chunk.len());
+            let to = (range.end - begin).min(chunk.len());
+            from..to
+        };
+
+        if first == last {
+            return match self.chunk(first) {
+                Some(chunk) => Cow::Borrowed(&chunk[within(first, chunk)]),
+                None => Cow::Borrowed(&[]),
+            };
+        }
+        let mut bytes = Vec::with_capacity(range.len());
+        for index in first..=last {
+            let Some(chunk) = self.chunk(index) else {
+                break;
+            };
+            bytes.extend_from_slice(&chunk[within(index, chunk)]);
+        }
+        Cow::Owned(bytes)
+    }
+
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
+
+impl fmt::Debug for LazyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let read = self.chunks.iter().filter(|chunk| chunk.get().is_some());
+        f.debug_struct("LazyFile")
+            .field("len", &self.len)
+            .field("chunks_read", &read.count())
+            .field("chunks", &self.chunks.len())
+            .finish()
+    }
+}
@@ -0,0 +1,197 @@
+//! Text strings, the kind of string that's meant to be read by people, e.g. in the document
+//! information dictionary or the outline.
+
+/// The characters that PDFDocEncoding has in place of the control characters 0x18 to 0x1F.
+const PDF_DOC_LOW: [char; 8] = ['˘', 'ˇ', 'ˆ', '˙', '˝', '˛', '˚', '˜'];
+
+/// The characters that PDFDocEncoding has for 0x80 to 0xA0. It leaves 0x9F undefined.
+const PDF_DOC_HIGH: [char; 33] = [
+    '•', '†', '‡', '…', '—', '–', 'ƒ', '⁄', '‹', '›', '−', '‰', '„', '“', '”', '‘', '’', '‚', '™',
+    'ﬁ', 'ﬂ', 'Ł', 'Œ', 'Š', 'Ÿ', 'Ž', 'ı', 'ł', 'œ', 'š', 'ž', '\u{9F}', '€',
+];
+
+/// The escape character that starts and ends a language escape in a Unicode text string.
+const ESCAPE: u8 = 0x1B;
+
+/// Where a text string switches language: the decoded text from `offset` (a byte index) on
+/// is in `language`, e.g. `en` or `ja-JP`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LanguageTag {
+    pub offset: usize,
+    pub language: String,
+}
+
+/// Decodes a text string. It's UTF-16BE if it starts with a byte order mark, UTF-8 if it
+/// starts with the UTF-8 one (which PDF 2.0 allows), and PDFDocEncoding otherwise. Language
+/// escapes are left out; see [`decode_text_string_with_languages`].
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    decode_text_string_with_languages(bytes).0
+}
+
+/// Decodes a text string like [`decode_text_string`], and also returns the language escapes
+/// in it. Only Unicode text strings can have these: the escape character, a two-letter
+/// ISO 639 language code, an optional two-letter ISO 3166 country code, and the escape
+/// character again.
+pub fn decode_text_string_with_languages(bytes: &[u8]) -> (String, Vec<LanguageTag>) {
+    if let Some(utf16) = bytes.strip_prefix(b"\xFE\xFF") {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        // Each unit of the language code holds two of its letters
+        let code = |units: &[u16]| {
+            let bytes: Vec<u8> = units.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+            language_code(&bytes)
+        };
+        return split_languages(&units, code, |units, text| {
+            text.extend(
+                char::decode_utf16(units.iter().copied())
+                    .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER)),
+            )
+        });
+    }
+    if let Some(utf8) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return split_languages(utf8, language_code, |bytes, text| {
+            text.push_str(&String::from_utf8_lossy(bytes))
+        });
+    }
+    let text = bytes.iter().map(|&byte| pdf_doc_char(byte)).collect();
+    (text, Vec::new())
+}
+
+/// Decodes `units` with `decode`, minus the language escapes in it, whose codes `code` reads.
+/// An escape character that doesn't start a valid escape is decoded like any other.
+fn split_languages<T: Copy + PartialEq + From<u8>>(
+    units: &[T],
+    code: impl Fn(&[T]) -> Option<String>,
+    decode: impl Fn(&[T], &mut String),
+) -> (String, Vec<LanguageTag>) {
+    let escape = T::from(ESCAPE);
+    let mut text = String::new();
+    let mut languages = Vec::new();
+    let mut rest = units;
+    // Where to look for the next escape from, past any that turned out not to be valid
+    let mut from = 0;
+    while let Some(start) = rest[from..].iter().position(|&unit| unit == escape) {
+        let start = from + start;
+        // The longest code, with a country, is four bytes
+        let code_len = rest[start + 1..]
+            .iter()
+            .take(5)
+            .position(|&unit| unit == escape);
+        let Some((len, language)) =
+            code_len.and_then(|len| Some((len, code(&rest[start + 1..start + 1 + len])?)))
+        else {
+            from = start + 1;
+            continue;
+        };
+        decode(&rest[..start], &mut text);
+        languages.push(LanguageTag {
+            offset: text.len(),
+            language,
+        });
+        rest = &rest[start + len + 2..];
+        from = 0;
+    }
+    decode(rest, &mut text);
+    (text, languages)
+}
+
+/// The language a language escape's code stands for, e.g. `b"jaJP"` is `ja-JP`.
+fn language_code(code: &[u8]) -> Option<String> {
+    if !code.iter().all(u8::is_ascii_alphabetic) {
+        return None;
+    }
+    let code = String::from_utf8_lossy(code);
+    match code.len() {
+        2 => Some(code.to_ascii_lowercase()),
+        4 => Some(format!(
+            "{}-{}",
+            code[..2].to_ascii_lowercase(),
+            code[2..].to_ascii_uppercase()
+        )),
+        _ => None,
+    }
+}
+
+/// Encodes `text` as a text string, the inverse of [`decode_text_string`]. It's written in
+/// PDFDocEncoding if it can be, and in UTF-16BE otherwise. Text with U+001B in it can't be
+/// encoded, since that's the escape character of language escapes, so this returns `None`.
+pub fn encode_text_string(text: &str) -> Option<Vec<u8>> {
+    if text.contains(ESCAPE as char) {
+        return None;
+    }
+    let pdf_doc: Option<Vec<u8>> = text.chars().map(pdf_doc_byte).collect();
+    match pdf_doc {
+        // Unless it would be mistaken for a byte order mark
+        Some(bytes) if !bytes.starts_with(b"\xFE\xFF") && !bytes.starts_with(b"\xEF\xBB\xBF") => {
+            Some(bytes)
+        }
+        _ => {
+            let mut bytes = vec![0xFE, 0xFF];
+            for unit in text.encode_utf16() {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+            Some(bytes)
+        }
+    }
+}
+
+/// The character `byte` stands for in PDFDocEncoding. The few bytes it leaves undefined are
+/// taken to be Latin-1, like the rest of the top half.
+fn pdf_doc_char(byte: u8) -> char {
+    match byte {
+        0x18..=0x1F => PDF_DOC_LOW[(byte - 0x18) as usize],
+        0x80..=0xA0 => PDF_DOC_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+/// The byte that stands for `ch` in PDFDocEncoding, if it has one. Undefined bytes aren't
+/// used, even though [`pdf_doc_char`] accepts them.
+fn pdf_doc_byte(ch: char) -> Option<u8> {
+    if let Some(i) = PDF_DOC_LOW.iter().position(|&low| low == ch) {
+        return Some(0x18 + i as u8);
+    }
+    if let Some(i) = PDF_DOC_HIGH.iter().position(|&high| high == ch) {
+        return (ch != '\u{9F}').then_some(0x80 + i as u8);
+    }
+    match ch as u32 {
+        0x09 | 0x0A | 0x0D | 0x20..=0x7E | 0xA1..=0xAC | 0xAE..=0xFF => Some(ch as u8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let texts = [
+            "",
+            "plain",
+            "Café • ™ ˘ €",
+            "日本語 😀",
+            // PDFDocEncoding for these would look like a byte order mark
+            "þÿab",
+            "ï»¿x",
+            // Not in PDFDocEncoding, although they're Latin-1
+            "\u{9F}\u{AD}\u{7}",
+        ];
+        for text in texts {
+            let encoded = encode_text_string(text).unwrap();
+            assert_eq!(
+                decode_text_string_with_languages(&encoded),
+                (text.to_owned(), vec![])
+            );
+        }
+    }
+
+    #[test]
+    fn escape_character() {
+        // In UTF-16BE, this would read back as a language escape for `en`
+        assert_eq!(encode_text_string("\u{1B}en\u{1B}日本"), None);
+        assert_eq!(encode_text_string("\u{1B}"), None);
+    }
+}
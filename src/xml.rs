@@ -0,0 +1,375 @@
+//! A small XML reader, enough for XMP metadata. It checks that tags are balanced and resolves
+//! namespaces, but ignores DTDs and doesn't validate anything.
+
+use core::fmt;
+use std::collections::HashMap;
+
+/// The namespace that the `xml` prefix is always bound to.
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// How deeply elements can be nested. XMP packets are never more than a few levels deep.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct XmlError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "XML error at index {}: {}", self.offset, self.message)
+    }
+}
+
+/// A name, with its prefix resolved to the namespace it stands for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QName {
+    /// The namespace URI, or empty if the name isn't in a namespace.
+    pub namespace: String,
+    pub prefix: String,
+    pub local: String,
+}
+
+impl QName {
+    pub fn is(&self, namespace: &str, local: &str) -> bool {
+        self.namespace == namespace && self.local == local
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub name: QName,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: QName,
+    /// The element's attributes, other than namespace declarations.
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Element(Element),
+    /// Character data, with entities and CDATA sections decoded.
+    Text(String),
+}
+
+impl Element {
+    pub fn attribute(&self, namespace: &str, local: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name.is(namespace, local))
+            .map(|attribute| attribute.value.as_str())
+    }
+
+    /// The child elements, skipping text.
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// All the text directly inside the element.
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// An element whose end tag hasn't been reached yet.
+struct Open {
+    element: Element,
+    /// The name as written in the start tag, to match against the end tag.
+    raw_name: String,
+    /// The namespace each prefix is bound to inside the element. The empty prefix is the
+    /// default namespace.
+    namespaces: HashMap<String, String>,
+}
+
+struct Reader<'t> {
+    text: &'t str,
+    cur: usize,
+}
+
+/// Parses an XML document, and returns its root element.
+pub fn parse(text: &str) -> Result<Element, XmlError> {
+    let mut reader = Reader { text, cur: 0 };
+    let mut open: Vec<Open> = Vec::new();
+    let mut root = None;
+    let top_namespaces = HashMap::from([("xml".to_owned(), XML_NAMESPACE.to_owned())]);
+
+    while reader.cur < text.len() {
+        let rest = reader.rest();
+        if rest.starts_with("<!--") {
+            reader.skip_past("-->", "end of comment")?;
+        } else if rest.starts_with("<![CDATA[") {
+            reader.cur += "<![CDATA[".len();
+            let begin = reader.cur;
+            reader.skip_past("]]>", "end of CDATA section")?;
+            let data = &text[begin..reader.cur - "]]>".len()];
+            if let Some(parent) = open.last_mut() {
+                parent.element.children.push(Node::Text(data.to_owned()));
+            }
+        } else if rest.starts_with("<?") {
+            reader.skip_past("?>", "end of processing instruction")?;
+        } else if rest.starts_with("<!") {
+            // A DOCTYPE, whose internal subset (if any) is in brackets before its first `>`
+            let head = &rest[..rest.find('>').unwrap_or(rest.len())];
+            if head.contains('[') {
+                reader.skip_past("]", "end of DOCTYPE internal subset")?;
+            }
+            reader.skip_past(">", "end of DOCTYPE")?;
+        } else if rest.starts_with("</") {
+            let begin = reader.cur;
+            reader.cur += 2;
+            let name = reader.chop_name();
+            reader.skip_whitespace();
+            reader.expect(">")?;
+            let Some(closed) = open.pop() else {
+                return Err(reader.error_at(begin, format!("`</{}>` without a start tag", name)));
+            };
+            if name != closed.raw_name {
+                return Err(reader.error_at(
+                    begin,
+                    format!("`<{}>` closed by `</{}>`", closed.raw_name, name),
+                ));
+            }
+            match open.last_mut() {
+                Some(parent) => parent.element.children.push(Node::Element(closed.element)),
+                None => root = Some(closed.element),
+            }
+        } else if rest.starts_with('<') {
+            let begin = reader.cur;
+            if root.is_some() && open.is_empty() {
+                return Err(reader.error_at(begin, "more than one root element"));
+            }
+            reader.cur += 1;
+            let raw_name = reader.chop_name().to_owned();
+            let mut raw_attributes = Vec::new();
+            let empty = loop {
+                reader.skip_whitespace();
+                if reader.rest().starts_with("/>") {
+                    reader.cur += 2;
+                    break true;
+                }
+                if reader.rest().starts_with('>') {
+                    reader.cur += 1;
+                    break false;
+                }
+                let name = reader.chop_name().to_owned();
+                if name.is_empty() {
+                    return Err(reader.error("an attribute name"));
+                }
+                reader.skip_whitespace();
+                reader.expect("=")?;
+                reader.skip_whitespace();
+                let value = reader.chop_quoted()?;
+                raw_attributes.push((name, value));
+            };
+
+            let parent = open
+                .last()
+                .map_or(&top_namespaces, |parent| &parent.namespaces);
+            let mut namespaces = parent.clone();
+            for (name, value) in &raw_attributes {
+                if name == "xmlns" {
+                    namespaces.insert(String::new(), value.clone());
+                } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+                    namespaces.insert(prefix.to_owned(), value.clone());
+                }
+            }
+            let name = resolve(&raw_name, &namespaces, true).ok_or_else(|| {
+                reader.error_at(begin, format!("unbound prefix in `{}`", raw_name))
+            })?;
+            let mut attributes = Vec::new();
+            for (raw, value) in raw_attributes {
+                if raw == "xmlns" || raw.starts_with("xmlns:") {
+                    continue;
+                }
+                let name = resolve(&raw, &namespaces, false).ok_or_else(|| {
+                    reader.error_at(begin, format!("unbound prefix in `{}`", raw))
+                })?;
+                attributes.push(Attribute { name, value });
+            }
+
+            let element = Element {
+                name,
+                attributes,
+                children: Vec::new(),
+            };
+            if empty {
+                match open.last_mut() {
+                    Some(parent) => parent.element.children.push(Node::Element(element)),
+                    None => root = Some(element),
+                }
+            } else {
+                if open.len() == MAX_DEPTH {
+                    return Err(reader.error_at(begin, "elements are nested too deeply"));
+                }
+                open.push(Open {
+                    element,
+                    raw_name,
+                    namespaces,
+                });
+            }
+        } else {
+            let begin = reader.cur;
+            let end = rest.find('<').map_or(text.len(), |end| begin + end);
+            reader.cur = end;
+            // Text outside the root element, e.g. an XMP packet's padding, is ignored
+            if let Some(parent) = open.last_mut() {
+                let text = unescape(&text[begin..end]).map_err(|message| XmlError {
+                    offset: begin,
+                    message,
+                })?;
+                parent.element.children.push(Node::Text(text));
+            }
+        }
+    }
+
+    if let Some(unclosed) = open.last() {
+        return Err(reader.error_at(
+            text.len(),
+            format!("`<{}>` is never closed", unclosed.raw_name),
+        ));
+    }
+    root.ok_or_else(|| reader.error("a root element"))
+}
+
+/// Splits `raw` into a prefix and local name, and looks up the prefix's namespace.
+/// Unprefixed attributes aren't in any namespace, even if there's a default one.
+fn resolve(raw: &str, namespaces: &HashMap<String, String>, element: bool) -> Option<QName> {
+    let (prefix, local) = raw.split_once(':').unwrap_or(("", raw));
+    let namespace = match namespaces.get(prefix) {
+        Some(namespace) if element || !prefix.is_empty() => namespace.clone(),
+        None if !prefix.is_empty() => return None,
+        _ => String::new(),
+    };
+    Some(QName {
+        namespace,
+        prefix: prefix.to_owned(),
+        local: local.to_owned(),
+    })
+}
+
+/// Decodes the entities and character references in `text`.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        let Some(semicolon) = rest[amp..].find(';') else {
+            return Err("`&` without a `;` after it".to_owned());
+        };
+        let entity = &rest[amp + 1..amp + semicolon];
+        let ch = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix('#') {
+                Some(number) => match number.strip_prefix('x') {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => number.parse().ok(),
+                }
+                .and_then(char::from_u32),
+                None => None,
+            },
+        };
+        match ch {
+            Some(ch) => result.push(ch),
+            None => return Err(format!("unknown entity `&{};`", entity)),
+        }
+        rest = &rest[amp + semicolon + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+impl<'t> Reader<'t> {
+    fn rest(&self) -> &str {
+        &self.text[self.cur..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.cur += rest.len() - rest.trim_start().len();
+    }
+
+    /// Moves past the next occurrence of `end`.
+    fn skip_past(&mut self, end: &str, expected: &str) -> Result<(), XmlError> {
+        match self.rest().find(end) {
+            Some(found) => {
+                self.cur += found + end.len();
+                Ok(())
+            }
+            None => Err(self.error_at(self.text.len(), expected)),
+        }
+    }
+
+    fn chop_name(&mut self) -> &'t str {
+        let rest = &self.text[self.cur..];
+        let len = rest
+            .find(|ch: char| ch.is_whitespace() || matches!(ch, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+        self.cur += len;
+        &rest[..len]
+    }
+
+    fn chop_quoted(&mut self) -> Result<String, XmlError> {
+        let begin = self.cur;
+        let quote = match self.rest().chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.error("a quoted attribute value")),
+        };
+        let Some(len) = self.rest()[1..].find(quote) else {
+            return Err(self.error_at(self.text.len(), "end of attribute value"));
+        };
+        let value = &self.text[begin + 1..begin + 1 + len];
+        self.cur = begin + len + 2;
+        unescape(value).map_err(|message| self.error_at(begin, message))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), XmlError> {
+        if !self.rest().starts_with(expected) {
+            return Err(self.error(&format!("`{}`", expected)));
+        }
+        self.cur += expected.len();
+        Ok(())
+    }
+
+    fn error(&self, expected: &str) -> XmlError {
+        self.error_at(self.cur, format!("expected {}", expected))
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> XmlError {
+        XmlError {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doctype() {
+        for doctype in ["<!DOCTYPE a>", "<!DOCTYPE a [<!ENTITY e \"v\">] >"] {
+            let root = parse(&format!("{}<a><![CDATA[[x]>]]></a>", doctype)).unwrap();
+            assert_eq!(root.text(), "[x]>");
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! XMP metadata: the RDF/XML packet in the catalog's `/Metadata` stream, which newer files
+//! use instead of (or as well as) the document information dictionary.
+
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::{
+    info::{Date, DocumentInfo},
+    xml::{self, Element, QName, XmlError, XML_NAMESPACE},
+    Document, Object, ParseError,
+};
+
+const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// The usual prefix for each well-known namespace. Properties in these namespaces are named
+/// with these prefixes, whichever ones the packet itself uses.
+const PREFIXES: &[(&str, &str)] = &[
+    ("http://purl.org/dc/elements/1.1/", "dc"),
+    ("http://ns.adobe.com/xap/1.0/", "xmp"),
+    ("http://ns.adobe.com/xap/1.0/mm/", "xmpMM"),
+    ("http://ns.adobe.com/xap/1.0/rights/", "xmpRights"),
+    ("http://ns.adobe.com/xap/1.0/sType/ResourceEvent#", "stEvt"),
+    ("http://ns.adobe.com/xap/1.0/sType/ResourceRef#", "stRef"),
+    ("http://ns.adobe.com/pdf/1.3/", "pdf"),
+    ("http://ns.adobe.com/pdfx/1.3/", "pdfx"),
+    ("http://ns.adobe.com/photoshop/1.0/", "photoshop"),
+    ("http://www.aiim.org/pdfa/ns/id/", "pdfaid"),
+    ("http://www.aiim.org/pdfua/ns/id/", "pdfuaid"),
+];
+
+/// The value of an XMP property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum XmpValue {
+    Text(String),
+    /// An `rdf:Seq` (ordered) or `rdf:Bag` (unordered) array.
+    List(Vec<XmpValue>),
+    /// An `rdf:Alt` of the same text in different languages, each with its `xml:lang`.
+    Alternatives(Vec<(String, XmpValue)>),
+    /// A structure's fields, named like properties.
+    Struct(BTreeMap<String, XmpValue>),
+}
+
+impl XmpValue {
+    /// The value as text: a simple value, or the `x-default` (or else the first) of a
+    /// language alternative.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            XmpValue::Text(text) => Some(text),
+            XmpValue::Alternatives(alternatives) => alternatives
+                .iter()
+                .find(|(lang, _)| lang == "x-default")
+                .or_else(|| alternatives.first())
+                .and_then(|(_, value)| value.as_text()),
+            XmpValue::List(_) | XmpValue::Struct(_) => None,
+        }
+    }
+}
+
+/// Arrays are written with their items separated by `; `, and language alternatives as
+/// their default text.
+impl fmt::Display for XmpValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmpValue::Text(text) => write!(f, "{}", text),
+            XmpValue::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                Ok(())
+            }
+            XmpValue::Alternatives(_) => write!(f, "{}", self.as_text().unwrap_or_default()),
+            XmpValue::Struct(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// An XMP packet's properties, named `prefix:name`, e.g. `dc:title` or `xmp:CreateDate`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Xmp {
+    pub properties: BTreeMap<String, XmpValue>,
+}
+
+impl Xmp {
+    /// Parses an XMP packet, which is usually UTF-8 but can be UTF-16 with a byte order mark.
+    pub fn parse(data: &[u8]) -> Result<Xmp, XmlError> {
+        let text = match data {
+            [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+            [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+            [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+            _ => String::from_utf8_lossy(data).into_owned(),
+        };
+        let root = xml::parse(&text)?;
+
+        // The RDF is usually wrapped in an `x:xmpmeta` element, but doesn't have to be
+        let mut properties = BTreeMap::new();
+        let mut elements = vec![&root];
+        while let Some(element) = elements.pop() {
+            if !element.name.is(RDF_NAMESPACE, "RDF") {
+                elements.extend(element.elements());
+                continue;
+            }
+            for description in element.elements() {
+                if description.name.is(RDF_NAMESPACE, "Description") {
+                    properties.extend(fields(description));
+                }
+            }
+        }
+        Ok(Xmp { properties })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&XmpValue> {
+        self.properties.get(name)
+    }
+
+    /// The property `name` as text. See [`XmpValue::as_text`].
+    pub fn text(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(XmpValue::as_text)
+    }
+
+    /// The property `name` as a date, if it's a valid one.
+    pub fn date(&self, name: &str) -> Option<Date> {
+        self.text(name).and_then(Date::parse_iso8601)
+    }
+
+    /// The PDF/A standard the document claims to conform to, e.g. `PDF/A-2B`.
+    pub fn pdfa(&self) -> Option<String> {
+        let part = self.text("pdfaid:part")?.trim();
+        let conformance = self.text("pdfaid:conformance").unwrap_or_default().trim();
+        Some(format!("PDF/A-{}{}", part, conformance.to_uppercase()))
+    }
+
+    /// Where the document information dictionary `info` and this metadata disagree, as the
+    /// PDF/A standards say they mustn't.
+    pub fn mismatches(&self, info: &DocumentInfo) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        let text = [
+            ("Title", &info.title, "dc:title"),
+            ("Author", &info.author, "dc:creator"),
+            ("Subject", &info.subject, "dc:description"),
+            ("Keywords", &info.keywords, "pdf:Keywords"),
+            ("Creator", &info.creator, "xmp:CreatorTool"),
+            ("Producer", &info.producer, "pdf:Producer"),
+            ("Trapped", &info.trapped, "pdf:Trapped"),
+        ];
+        for (key, value, property) in text {
+            // Arrays are compared by their first item: `/Author` is the first `dc:creator`
+            let xmp = match self.get(property) {
+                Some(XmpValue::List(items)) => items.first().map(XmpValue::to_string),
+                value => value.map(XmpValue::to_string),
+            };
+            match (value, xmp) {
+                (Some(value), Some(xmp)) if *value != xmp => mismatches.push(format!(
+                    "`/{}` is {:?}, but `{}` in the XMP metadata is {:?}",
+                    key, value, property, xmp
+                )),
+                (Some(value), None) => mismatches.push(format!(
+                    "`/{}` is {:?}, but the XMP metadata has no `{}`",
+                    key, value, property
+                )),
+                (None, Some(xmp)) => mismatches.push(format!(
+                    "`{}` in the XMP metadata is {:?}, but there's no `/{}`",
+                    property, xmp, key
+                )),
+                _ => {}
+            }
+        }
+
+        let dates = [
+            ("CreationDate", info.creation_date, "xmp:CreateDate"),
+            ("ModDate", info.mod_date, "xmp:ModifyDate"),
+        ];
+        for (key, value, property) in dates {
+            match (value, self.date(property)) {
+                (Some(value), Some(xmp)) if !value.same_time(&xmp) => mismatches.push(format!(
+                    "`/{}` is {}, but `{}` in the XMP metadata is {}",
+                    key, value, property, xmp
+                )),
+                (Some(value), None) => mismatches.push(format!(
+                    "`/{}` is {}, but the XMP metadata has no valid `{}`",
+                    key, value, property
+                )),
+                (None, Some(xmp)) => mismatches.push(format!(
+                    "`{}` in the XMP metadata is {}, but there's no valid `/{}`",
+                    property, xmp, key
+                )),
+                _ => {}
+            }
+        }
+        mismatches
+    }
+}
+
+fn utf16(data: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = data
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// The name a property or structure field is known by.
+fn property_name(name: &QName) -> String {
+    let prefix = PREFIXES
+        .iter()
+        .find(|(namespace, _)| *namespace == name.namespace)
+        .map_or(name.prefix.as_str(), |(_, prefix)| prefix);
+    match prefix {
+        "" => name.local.clone(),
+        prefix => format!("{}:{}", prefix, name.local),
+    }
+}
+
+/// The properties of an `rdf:Description`, or the fields of a structure: its attributes (the
+/// shorthand for simple values) and its child elements.
+fn fields(element: &Element) -> BTreeMap<String, XmpValue> {
+    let mut fields = BTreeMap::new();
+    for attribute in &element.attributes {
+        let namespace = attribute.name.namespace.as_str();
+        if !namespace.is_empty() && namespace != RDF_NAMESPACE && namespace != XML_NAMESPACE {
+            let value = XmpValue::Text(attribute.value.clone());
+            fields.insert(property_name(&attribute.name), value);
+        }
+    }
+    for child in element.elements() {
+        if child.name.namespace != RDF_NAMESPACE {
+            fields.insert(property_name(&child.name), value(child));
+        }
+    }
+    fields
+}
+
+/// The value of a property element, or of an `rdf:li` in an array.
+fn value(element: &Element) -> XmpValue {
+    if let Some(resource) = element.attribute(RDF_NAMESPACE, "resource") {
+        return XmpValue::Text(resource.to_owned());
+    }
+    if element.attribute(RDF_NAMESPACE, "parseType") == Some("Resource") {
+        return XmpValue::Struct(fields(element));
+    }
+    if let Some(inner) = element.elements().next() {
+        let items = || {
+            inner
+                .elements()
+                .filter(|item| item.name.is(RDF_NAMESPACE, "li"))
+        };
+        return match inner.name.local.as_str() {
+            "Seq" | "Bag" if inner.name.namespace == RDF_NAMESPACE => {
+                XmpValue::List(items().map(value).collect())
+            }
+            "Alt" if inner.name.namespace == RDF_NAMESPACE => XmpValue::Alternatives(
+                items()
+                    .map(|item| {
+                        let lang = item.attribute(XML_NAMESPACE, "lang").unwrap_or_default();
+                        (lang.to_owned(), value(item))
+                    })
+                    .collect(),
+            ),
+            "Description" if inner.name.namespace == RDF_NAMESPACE => {
+                XmpValue::Struct(fields(inner))
+            }
+            _ => XmpValue::Struct(fields(element)),
+        };
+    }
+    // A structure can be written as just its fields' attributes
+    let fields = fields(element);
+    if !fields.is_empty() {
+        return XmpValue::Struct(fields);
+    }
+    XmpValue::Text(element.text())
+}
+
+impl Document<'_> {
+    /// The catalog's XMP metadata, or `None` if there isn't any.
+    pub fn xmp(&mut self) -> Result<Option<Xmp>, ParseError> {
+        let Some(Object::Reference(id)) = self.catalog()?.get("Metadata").cloned() else {
+            return Ok(None);
+        };
+        let id = self.follow(id)?;
+        let Object::Stream {
+            dict,
+            data,
+            decoded,
+        } = self.get_object(id)?
+        else {
+            return Ok(None);
+        };
+        let data = match decoded {
+            Some(decoded) => decoded.clone(),
+            None => {
+                let (dict, data) = (dict.clone(), data.clone());
+                self.parser.stream_contents(id, &dict, data)?
+            }
+        };
+        Xmp::parse(&data).map(Some).map_err(ParseError::Xml)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about=""
+        xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+        xmlns:pdf="http://ns.adobe.com/pdf/1.3/"
+        xmlns:id="http://www.aiim.org/pdfa/ns/id/"
+        xmp:CreateDate="2023-04-05T06:07:08+02:00"
+        pdf:Producer="freed"
+        id:part="2" id:conformance="b"/>
+    <rdf:Description rdf:about=""
+        xmlns:dc="http://purl.org/dc/elements/1.1/"
+        xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+      <dc:title>
+        <rdf:Alt>
+          <rdf:li xml:lang="fr">Le titre</rdf:li>
+          <rdf:li xml:lang="x-default">The title</rdf:li>
+        </rdf:Alt>
+      </dc:title>
+      <dc:creator>
+        <rdf:Seq><rdf:li>Ann</rdf:li><rdf:li>Bob</rdf:li></rdf:Seq>
+      </dc:creator>
+      <xmp:CreatorTool>Writer &amp; co</xmp:CreatorTool>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn properties() {
+        let xmp = Xmp::parse(PACKET.as_bytes()).unwrap();
+        // Attributes, named with the usual prefix rather than the packet's
+        assert_eq!(xmp.text("pdf:Producer"), Some("freed"));
+        assert_eq!(xmp.pdfa().as_deref(), Some("PDF/A-2B"));
+        assert_eq!(
+            xmp.date("xmp:CreateDate"),
+            Date::parse("D:20230405060708+02'00'")
+        );
+        // and elements
+        assert_eq!(xmp.text("xmp:CreatorTool"), Some("Writer & co"));
+        assert_eq!(xmp.text("dc:title"), Some("The title"));
+        assert_eq!(
+            xmp.get("dc:creator"),
+            Some(&XmpValue::List(vec![
+                XmpValue::Text("Ann".to_owned()),
+                XmpValue::Text("Bob".to_owned()),
+            ]))
+        );
+        assert_eq!(xmp.get("dc:creator").unwrap().to_string(), "Ann; Bob");
+        assert_eq!(xmp.text("dc:creator"), None);
+
+        // The same packet in UTF-16
+        let mut utf16 = vec![0xFE, 0xFF];
+        utf16.extend(PACKET.encode_utf16().flat_map(u16::to_be_bytes));
+        assert_eq!(Xmp::parse(&utf16).unwrap(), xmp);
+    }
+
+    #[test]
+    fn language_alternatives() {
+        // Without an `x-default`, the first alternative is the text
+        let alternatives = XmpValue::Alternatives(vec![
+            ("de".to_owned(), XmpValue::Text("Titel".to_owned())),
+            ("fr".to_owned(), XmpValue::Text("Titre".to_owned())),
+        ]);
+        assert_eq!(alternatives.as_text(), Some("Titel"));
+        assert_eq!(XmpValue::Alternatives(Vec::new()).as_text(), None);
+    }
+
+    #[test]
+    fn mismatches() {
+        let xmp = Xmp::parse(PACKET.as_bytes()).unwrap();
+        let mut info = DocumentInfo {
+            title: Some("The title".to_owned()),
+            author: Some("Ann".to_owned()),
+            creator: Some("Writer & co".to_owned()),
+            producer: Some("freed".to_owned()),
+            // The same moment in another time zone
+            creation_date: Date::parse("D:20230405100708+06'00'"),
+            ..DocumentInfo::default()
+        };
+        assert_eq!(xmp.mismatches(&info), Vec::<String>::new());
+
+        info.author = Some("Ann; Bob".to_owned());
+        info.producer = None;
+        info.creation_date = Date::parse("D:20230405060708+06'00'");
+        info.mod_date = Date::parse("D:2024");
+        assert_eq!(
+            xmp.mismatches(&info),
+            [
+                "`/Author` is \"Ann; Bob\", but `dc:creator` in the XMP metadata is \"Ann\"",
+                "`pdf:Producer` in the XMP metadata is \"freed\", but there's no `/Producer`",
+                "`/CreationDate` is 2023-04-05T06:07:08+06:00, but `xmp:CreateDate` in the XMP \
+                 metadata is 2023-04-05T06:07:08+02:00",
+                "`/ModDate` is 2024-01-01T00:00:00, but the XMP metadata has no valid \
+                 `xmp:ModifyDate`",
+            ]
+        );
+    }
+}